@@ -13,26 +13,67 @@
 //! improves from-scratch compilation time, which matters for `cargo
 //! install`.
 
+use release_utils::Identity;
+use release_utils::github::Gh;
+use release_utils::release::{PreflightChecks, TagBackend};
+use std::path::PathBuf;
 use std::{env, process};
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Condition {
     Body,
     Subject,
+    Trailer,
 }
 
 #[derive(Default, Debug, Eq, PartialEq)]
 pub struct Cli {
     pub package: Vec<String>,
     pub condition: Option<Condition>,
+    pub local: bool,
+    pub allow_branch: Vec<String>,
+    pub allow_tag: Vec<String>,
+    pub annotate: bool,
+    pub tag_message: Option<String>,
+    pub changelog: Option<PathBuf>,
+    pub sign: bool,
+    pub tagger: Option<Identity>,
+    pub remote: Option<String>,
+    pub tag_format: Option<String>,
+    pub preflight: PreflightChecks,
+    pub notes_ref: Option<String>,
+    pub tag_backend: TagBackend,
 }
 
 const USAGE: &str = r#"Usage:
-auto-release -p <PKG> [-p <PKG>...] [--condition body|subject]
+auto-release -p <PKG> [-p <PKG>...] [--condition body|subject|trailer]
+             [--local]
+             [--allow-branch <GLOB>...] [--allow-tag <GLOB>...]
+             [--annotate] [--tag-message <TEMPLATE>] [--changelog <PATH>]
+             [--sign] [--tagger <NAME <EMAIL>>] [--remote <REMOTE>]
+             [--tag-format <FORMAT>] [--preflight <CHECK>...]
+             [--notes-ref <REF>] [--tag-backend git|github]
 
 Options:
   -p, --package <PACKAGE>
-      --condition <CONDITION>  [possible values: body, subject]
+      --condition <CONDITION>  [possible values: body, subject, trailer]
+      --local                  Release HEAD of the local checkout
+      --allow-branch <GLOB>    Only release from matching branches
+      --allow-tag <GLOB>       Only release from matching tags
+      --annotate               Create annotated tags
+      --tag-message <TEMPLATE> Message template for annotated tags
+      --changelog <PATH>       Changelog to excerpt in annotated tags
+      --sign                   Sign tags (key from RELEASE_UTILS_SIGNING_KEY)
+      --tagger <NAME <EMAIL>>  Identity of the tagger
+      --remote <REMOTE>        Remote to push tags to [default: origin]
+      --tag-format <FORMAT>    Format of tag names [default: {name}-v{version}]
+      --preflight <CHECK>      Check the repo before releasing
+                               [possible values: clean, head,
+                               default-branch, no-operation, all]
+      --notes-ref <REF>        Record releases in git notes, e.g.
+                               refs/notes/releases
+      --tag-backend <BACKEND>  How to create tags [default: git]
+                               [possible values: git, github]
   -h, --help                   Print help
 "#;
 
@@ -40,14 +81,29 @@ enum ArgState {
     Any,
     Package,
     Condition,
+    AllowBranch,
+    AllowTag,
+    TagMessage,
+    Changelog,
+    Tagger,
+    Remote,
+    TagFormat,
+    Preflight,
+    NotesRef,
+    TagBackend,
 }
 
+// Only one of these is ever created, so the size doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Eq, PartialEq)]
 enum ArgParseResult {
     Success(Cli),
     ShowUsage,
     InvalidArg,
     InvalidCondition,
+    InvalidTagger,
+    InvalidPreflightCheck,
+    InvalidTagBackend,
     MissingValue,
     MissingPackage,
 }
@@ -69,6 +125,32 @@ fn parse_args_from_iter(
                     arg_state = ArgState::Package;
                 } else if arg == "--condition" {
                     arg_state = ArgState::Condition;
+                } else if arg == "--local" {
+                    cli.local = true;
+                } else if arg == "--allow-branch" {
+                    arg_state = ArgState::AllowBranch;
+                } else if arg == "--allow-tag" {
+                    arg_state = ArgState::AllowTag;
+                } else if arg == "--annotate" {
+                    cli.annotate = true;
+                } else if arg == "--tag-message" {
+                    arg_state = ArgState::TagMessage;
+                } else if arg == "--changelog" {
+                    arg_state = ArgState::Changelog;
+                } else if arg == "--sign" {
+                    cli.sign = true;
+                } else if arg == "--tagger" {
+                    arg_state = ArgState::Tagger;
+                } else if arg == "--remote" {
+                    arg_state = ArgState::Remote;
+                } else if arg == "--tag-format" {
+                    arg_state = ArgState::TagFormat;
+                } else if arg == "--preflight" {
+                    arg_state = ArgState::Preflight;
+                } else if arg == "--notes-ref" {
+                    arg_state = ArgState::NotesRef;
+                } else if arg == "--tag-backend" {
+                    arg_state = ArgState::TagBackend;
                 } else if arg == "-h" || arg == "--help" {
                     return ArgParseResult::ShowUsage;
                 } else {
@@ -84,11 +166,68 @@ fn parse_args_from_iter(
                     cli.condition = Some(Condition::Body);
                 } else if arg == "subject" {
                     cli.condition = Some(Condition::Subject);
+                } else if arg == "trailer" {
+                    cli.condition = Some(Condition::Trailer);
                 } else {
                     return ArgParseResult::InvalidCondition;
                 }
                 arg_state = ArgState::Any;
             }
+            ArgState::AllowBranch => {
+                cli.allow_branch.push(arg);
+                arg_state = ArgState::Any;
+            }
+            ArgState::AllowTag => {
+                cli.allow_tag.push(arg);
+                arg_state = ArgState::Any;
+            }
+            ArgState::TagMessage => {
+                cli.tag_message = Some(arg);
+                arg_state = ArgState::Any;
+            }
+            ArgState::Changelog => {
+                cli.changelog = Some(PathBuf::from(arg));
+                arg_state = ArgState::Any;
+            }
+            ArgState::Tagger => {
+                let Some(tagger) = Identity::parse(&arg) else {
+                    return ArgParseResult::InvalidTagger;
+                };
+                cli.tagger = Some(tagger);
+                arg_state = ArgState::Any;
+            }
+            ArgState::Remote => {
+                cli.remote = Some(arg);
+                arg_state = ArgState::Any;
+            }
+            ArgState::TagFormat => {
+                cli.tag_format = Some(arg);
+                arg_state = ArgState::Any;
+            }
+            ArgState::Preflight => {
+                let checks = &mut cli.preflight;
+                match arg.as_str() {
+                    "clean" => checks.clean_work_tree = true,
+                    "head" => checks.head_is_commit = true,
+                    "default-branch" => checks.commit_on_default_branch = true,
+                    "no-operation" => checks.no_operation_in_progress = true,
+                    "all" => *checks = PreflightChecks::all(),
+                    _ => return ArgParseResult::InvalidPreflightCheck,
+                }
+                arg_state = ArgState::Any;
+            }
+            ArgState::NotesRef => {
+                cli.notes_ref = Some(arg);
+                arg_state = ArgState::Any;
+            }
+            ArgState::TagBackend => {
+                cli.tag_backend = match arg.as_str() {
+                    "git" => TagBackend::Git,
+                    "github" => TagBackend::Github(Gh::new()),
+                    _ => return ArgParseResult::InvalidTagBackend,
+                };
+                arg_state = ArgState::Any;
+            }
         }
     }
 
@@ -115,6 +254,9 @@ pub fn parse_args() -> Cli {
         }
         ArgParseResult::InvalidArg => "invalid arg",
         ArgParseResult::InvalidCondition => "invalid condition",
+        ArgParseResult::InvalidTagger => "invalid tagger",
+        ArgParseResult::InvalidPreflightCheck => "invalid preflight check",
+        ArgParseResult::InvalidTagBackend => "invalid tag backend",
         ArgParseResult::MissingValue => "missing arg value",
         ArgParseResult::MissingPackage => {
             "at least one package must be specified"
@@ -173,7 +315,7 @@ mod tests {
             parse_args_from_iter(args(&["auto-release", "-p", "foo"])),
             ArgParseResult::Success(Cli {
                 package: vec!["foo".to_string()],
-                condition: None,
+                ..Default::default()
             })
         );
 
@@ -187,7 +329,7 @@ mod tests {
             ])),
             ArgParseResult::Success(Cli {
                 package: vec!["foo".to_string(), "bar".to_string()],
-                condition: None,
+                ..Default::default()
             })
         );
 
@@ -202,6 +344,7 @@ mod tests {
             ArgParseResult::Success(Cli {
                 package: vec!["foo".to_string()],
                 condition: Some(Condition::Body),
+                ..Default::default()
             })
         );
 
@@ -216,9 +359,200 @@ mod tests {
             ArgParseResult::Success(Cli {
                 package: vec!["foo".to_string()],
                 condition: Some(Condition::Subject),
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            parse_args_from_iter(args(&[
+                "auto-release",
+                "-p",
+                "foo",
+                "--condition",
+                "trailer"
+            ])),
+            ArgParseResult::Success(Cli {
+                package: vec!["foo".to_string()],
+                condition: Some(Condition::Trailer),
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            parse_args_from_iter(args(&[
+                "auto-release",
+                "-p",
+                "foo",
+                "--local"
+            ])),
+            ArgParseResult::Success(Cli {
+                package: vec!["foo".to_string()],
+                local: true,
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            parse_args_from_iter(args(&[
+                "auto-release",
+                "-p",
+                "foo",
+                "--allow-branch",
+                "main",
+                "--allow-branch",
+                "release/*",
+                "--allow-tag",
+                "v*"
+            ])),
+            ArgParseResult::Success(Cli {
+                package: vec!["foo".to_string()],
+                allow_branch: vec!["main".to_string(), "release/*".to_string()],
+                allow_tag: vec!["v*".to_string()],
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            parse_args_from_iter(args(&["auto-release", "--allow-tag"])),
+            ArgParseResult::MissingValue
+        );
+
+        assert_eq!(
+            parse_args_from_iter(args(&[
+                "auto-release",
+                "-p",
+                "foo",
+                "--annotate",
+                "--tag-message",
+                "{package} {version}",
+                "--changelog",
+                "CHANGELOG.md",
+                "--sign",
+                "--tagger",
+                "Release Bot <bot@example.com>"
+            ])),
+            ArgParseResult::Success(Cli {
+                package: vec!["foo".to_string()],
+                annotate: true,
+                tag_message: Some("{package} {version}".to_string()),
+                changelog: Some(PathBuf::from("CHANGELOG.md")),
+                sign: true,
+                tagger: Some(Identity::new("Release Bot", "bot@example.com")),
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            parse_args_from_iter(args(&["auto-release", "--tagger", "bot"])),
+            ArgParseResult::InvalidTagger
+        );
+
+        assert_eq!(
+            parse_args_from_iter(args(&[
+                "auto-release",
+                "-p",
+                "foo",
+                "--remote",
+                "upstream"
+            ])),
+            ArgParseResult::Success(Cli {
+                package: vec!["foo".to_string()],
+                remote: Some("upstream".to_string()),
+                ..Default::default()
             })
         );
 
+        assert_eq!(
+            parse_args_from_iter(args(&[
+                "auto-release",
+                "-p",
+                "foo",
+                "--tag-format",
+                "v{version}"
+            ])),
+            ArgParseResult::Success(Cli {
+                package: vec!["foo".to_string()],
+                tag_format: Some("v{version}".to_string()),
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            parse_args_from_iter(args(&[
+                "auto-release",
+                "-p",
+                "foo",
+                "--preflight",
+                "clean",
+                "--preflight",
+                "default-branch"
+            ])),
+            ArgParseResult::Success(Cli {
+                package: vec!["foo".to_string()],
+                preflight: PreflightChecks {
+                    clean_work_tree: true,
+                    commit_on_default_branch: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            parse_args_from_iter(args(&[
+                "auto-release",
+                "-p",
+                "foo",
+                "--preflight",
+                "all"
+            ])),
+            ArgParseResult::Success(Cli {
+                package: vec!["foo".to_string()],
+                preflight: PreflightChecks::all(),
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            parse_args_from_iter(args(&["auto-release", "--preflight", "x"])),
+            ArgParseResult::InvalidPreflightCheck
+        );
+
+        assert_eq!(
+            parse_args_from_iter(args(&[
+                "auto-release",
+                "-p",
+                "foo",
+                "--notes-ref",
+                "refs/notes/releases"
+            ])),
+            ArgParseResult::Success(Cli {
+                package: vec!["foo".to_string()],
+                notes_ref: Some("refs/notes/releases".to_string()),
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            parse_args_from_iter(args(&[
+                "auto-release",
+                "-p",
+                "foo",
+                "--tag-backend",
+                "github"
+            ])),
+            ArgParseResult::Success(Cli {
+                package: vec!["foo".to_string()],
+                tag_backend: TagBackend::Github(Gh::new()),
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(
+            parse_args_from_iter(args(&["auto-release", "--tag-backend", "x"])),
+            ArgParseResult::InvalidTagBackend
+        );
+
         assert_eq!(
             parse_args_from_iter(args(&["auto-release", "-h"])),
             ArgParseResult::ShowUsage
@@ -11,28 +11,93 @@
 mod args;
 
 use args::{Cli, Condition, parse_args};
-use release_utils::release::release_packages;
-use release_utils::{Package, Repo, get_github_sha};
-use std::process;
+use release_utils::changes::get_package_changes;
+use release_utils::env::{
+    EventCommit, GithubEvent, append_github_step_summary,
+    detect_ci_environment, read_github_event, set_github_output,
+};
+use release_utils::release::{
+    PackageRelease, RefPolicy, ReleaseOptions, ReleasePackageError,
+    ReleasePackagesError, TagConfig, release_packages_with_options,
+};
+use release_utils::workflow::Annotation;
+use release_utils::{
+    GetLocalVersionError, Identity, Package, Repo, TagSigning, Trailer,
+};
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
 
 type Error = Box<dyn std::error::Error>;
 
-fn check_condition(condition: Condition) -> Result<bool, Error> {
-    let commit_sha = get_github_sha()?;
-    let repo = Repo::open()?;
+/// Get the commit from the GitHub Actions push event payload, if
+/// available.
+///
+/// The payload includes the full commit message even when the checkout
+/// is shallow.
+fn get_event_commit(commit_sha: &str) -> Option<EventCommit> {
+    match read_github_event() {
+        Ok(GithubEvent::Push(event)) => event.find_commit(commit_sha).cloned(),
+        _ => None,
+    }
+}
+
+/// Get the SHA of the commit to release, and its event payload if
+/// available.
+fn get_condition_commit(
+    repo: &Repo,
+    local: bool,
+) -> Result<(String, Option<EventCommit>), Error> {
+    if local {
+        return Ok((repo.head_sha()?, None));
+    }
+    let commit_sha = detect_ci_environment().commit_sha()?;
+    let event_commit = get_event_commit(&commit_sha);
+    Ok((commit_sha, event_commit))
+}
+
+/// Part of the commit message checked for the "release:" prefix.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum MessagePart {
+    Body,
+    Subject,
+}
+
+/// Open the repo in the current directory, fetching missing history
+/// from `remote` (defaults to `origin`).
+fn open_repo(remote: Option<&str>) -> Result<Repo, Error> {
+    let mut repo = Repo::open()?;
+    if let Some(remote) = remote {
+        repo.set_history_remote(remote);
+    }
+    Ok(repo)
+}
+
+fn check_condition(
+    part: MessagePart,
+    local: bool,
+    remote: Option<&str>,
+) -> Result<bool, Error> {
+    let repo = open_repo(remote)?;
+    let (commit_sha, event_commit) = get_condition_commit(&repo, local)?;
 
     let prefix = "release:";
 
     let msg_text;
     let msg_kind;
 
-    match condition {
-        Condition::Body => {
-            msg_text = repo.get_commit_message_body(&commit_sha)?;
+    match part {
+        MessagePart::Body => {
+            msg_text = match &event_commit {
+                Some(commit) => commit.body(),
+                None => repo.get_commit_message_body(&commit_sha)?,
+            };
             msg_kind = "body";
         }
-        Condition::Subject => {
-            msg_text = repo.get_commit_message_subject(&commit_sha)?;
+        MessagePart::Subject => {
+            msg_text = match &event_commit {
+                Some(commit) => commit.subject(),
+                None => repo.get_commit_message_subject(&commit_sha)?,
+            };
             msg_kind = "subject";
         }
     }
@@ -47,30 +112,453 @@ fn check_condition(condition: Condition) -> Result<bool, Error> {
     }
 }
 
+/// Trailer listing the packages to release for `--condition trailer`.
+const RELEASE_TRAILER: &str = "Release";
+
+/// Select the packages to release from the `Release` trailers in
+/// the commit message, e.g. `Release: foo, bar`.
+///
+/// Returns the selected packages, in the order of `packages`, and any
+/// names in the trailers that aren't in `packages`.
+fn select_trailer_packages(
+    packages: &[String],
+    trailers: &[Trailer],
+) -> (Vec<String>, Vec<String>) {
+    let names: Vec<_> = trailers
+        .iter()
+        .filter(|trailer| trailer.has_key(RELEASE_TRAILER))
+        .flat_map(|trailer| trailer.value.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    let selected = packages
+        .iter()
+        .filter(|package| names.contains(&package.as_str()))
+        .cloned()
+        .collect();
+    let mut unknown: Vec<_> = names
+        .into_iter()
+        .filter(|name| !packages.iter().any(|package| package == name))
+        .map(str::to_string)
+        .collect();
+    unknown.sort();
+    unknown.dedup();
+    (selected, unknown)
+}
+
+/// Get the packages to release for `--condition trailer`.
+fn get_trailer_packages(
+    packages: &[String],
+    local: bool,
+    remote: Option<&str>,
+) -> Result<Vec<String>, Error> {
+    let repo = open_repo(remote)?;
+    let (commit_sha, event_commit) = get_condition_commit(&repo, local)?;
+    let trailers = match &event_commit {
+        Some(commit) => repo.parse_trailers(&commit.message)?,
+        None => repo.get_commit_trailers(&commit_sha)?,
+    };
+
+    let (selected, unknown) = select_trailer_packages(packages, &trailers);
+    for name in unknown {
+        let msg = format!(
+            "ignoring package {name} in the {RELEASE_TRAILER} trailer, it was not passed with -p"
+        );
+        if is_github_actions() {
+            Annotation::warning(msg).emit();
+        } else {
+            println!("warning: {msg}");
+        }
+    }
+    if selected.is_empty() {
+        println!(
+            "commit {commit_sha} has no {RELEASE_TRAILER} trailer for any package"
+        );
+    }
+    Ok(selected)
+}
+
 fn execute(cli: Cli) -> Result<(), Error> {
-    if let Some(condition) = cli.condition {
-        if !check_condition(condition)? {
-            return Ok(());
+    let message_condition = |part| -> Result<Vec<String>, Error> {
+        if check_condition(part, cli.local, cli.remote.as_deref())? {
+            Ok(cli.package.clone())
+        } else {
+            Ok(Vec::new())
+        }
+    };
+    let names = match cli.condition {
+        None => cli.package.clone(),
+        Some(Condition::Body) => message_condition(MessagePart::Body)?,
+        Some(Condition::Subject) => message_condition(MessagePart::Subject)?,
+        Some(Condition::Trailer) => get_trailer_packages(
+            &cli.package,
+            cli.local,
+            cli.remote.as_deref(),
+        )?,
+    };
+
+    let skipped: Vec<_> = cli
+        .package
+        .iter()
+        .filter(|name| !names.contains(name))
+        .cloned()
+        .collect();
+    write_skipped_outputs(&skipped)?;
+    if names.is_empty() {
+        return Ok(());
+    }
+
+    let packages = names
+        .iter()
+        .map(|name| {
+            let mut package = Package::new(name);
+            if let Some(format) = &cli.tag_format {
+                package.set_tag_format(format)?;
+            }
+            Ok(package)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let opt = ReleaseOptions {
+        local: cli.local,
+        ref_policy: get_ref_policy(cli.allow_branch, cli.allow_tag),
+        tag: get_tag_config(
+            cli.annotate,
+            cli.tag_message,
+            cli.changelog,
+            cli.sign,
+            cli.tagger,
+        )?,
+        remote: cli.remote,
+        preflight: cli.preflight,
+        notes_ref: cli.notes_ref,
+        tag_backend: cli.tag_backend,
+    };
+
+    let releases = release_packages_with_options(&packages, &opt)?;
+    warn_unreleased_changes(&packages, &releases, &opt);
+
+    if is_github_actions() {
+        for release in releases.iter().filter(|r| r.is_new()) {
+            Annotation::notice(format!(
+                "released {} {}",
+                release.package, release.version
+            ))
+            .emit();
+        }
+    }
+    if env::var_os("GITHUB_OUTPUT").is_some() {
+        write_outputs(&releases)?;
+    }
+    if env::var_os("GITHUB_STEP_SUMMARY").is_some() {
+        append_github_step_summary(&format_summary(&releases))?;
+    }
+
+    Ok(())
+}
+
+/// Get the ref policy from the `--allow-branch` and `--allow-tag`
+/// args. If either is used, only the listed refs may release.
+fn get_ref_policy(branches: Vec<String>, tags: Vec<String>) -> RefPolicy {
+    if branches.is_empty() && tags.is_empty() {
+        RefPolicy::default()
+    } else {
+        RefPolicy {
+            branches: Some(branches),
+            tags: Some(tags),
+        }
+    }
+}
+
+/// Get the tag config from the tag-related args.
+fn get_tag_config(
+    annotate: bool,
+    message: Option<String>,
+    changelog: Option<PathBuf>,
+    sign: bool,
+    tagger: Option<Identity>,
+) -> Result<TagConfig, Error> {
+    let signing = if sign {
+        // Fall back to git's default signing key.
+        Some(TagSigning::from_env()?.unwrap_or_default())
+    } else {
+        None
+    };
+    let annotated = annotate || message.is_some() || changelog.is_some();
+
+    // Github Actions runners don't have a git identity configured,
+    // which annotated tags require.
+    let tagger = tagger.or_else(|| {
+        ((annotated || signing.is_some()) && is_github_actions())
+            .then(Identity::github_actions_bot)
+    });
+
+    Ok(TagConfig {
+        annotated,
+        message,
+        changelog,
+        signing,
+        tagger,
+    })
+}
+
+/// Print a warning for each package that was already released at its
+/// current version, but has changed since that release.
+///
+/// This usually means the version should have been bumped. Errors are
+/// printed rather than returned, since the release has already
+/// happened. The check is skipped in shallow clones.
+fn warn_unreleased_changes(
+    packages: &[Package],
+    releases: &[PackageRelease],
+    opt: &ReleaseOptions,
+) {
+    // Packages whose tag already existed were not bumped.
+    let unbumped: Vec<_> = packages
+        .iter()
+        .filter(|package| {
+            releases
+                .iter()
+                .any(|r| r.package == package.name() && !r.tagged)
+        })
+        .cloned()
+        .collect();
+    if unbumped.is_empty() {
+        return;
+    }
+    // The existing tags were only checked on the remote, so fetch them
+    // to compare against.
+    let tags: Vec<_> = releases
+        .iter()
+        .filter(|r| !r.tagged)
+        .map(|r| r.tag.as_str())
+        .collect();
+    let remote = opt.remote.as_deref().unwrap_or("origin");
+
+    let changes = open_repo(Some(remote)).and_then(|repo| {
+        // Comparing against the tags needs the full history. A warning
+        // isn't worth unshallowing the repo for.
+        if repo.is_shallow()? {
+            return Ok(None);
+        }
+        repo.fetch_tags(remote, &tags)?;
+        Ok(Some(get_package_changes(&repo, &unbumped, "HEAD", false)?))
+    });
+    let changes = match changes {
+        Ok(Some(changes)) => changes,
+        Ok(None) => {
+            println!(
+                "skipping the check for unreleased changes in a shallow clone"
+            );
+            return;
+        }
+        Err(err) => {
+            println!("failed to check for unreleased changes: {err}");
+            return;
+        }
+    };
+
+    for changes in changes.iter().filter(|c| c.has_changes()) {
+        let msg = format!(
+            "package {} has unreleased changes but its version wasn't bumped",
+            changes.package
+        );
+        if is_github_actions() {
+            Annotation::warning(msg).emit();
+        } else {
+            println!("warning: {msg}");
+        }
+    }
+}
+
+/// Set step outputs describing each release.
+fn write_outputs(releases: &[PackageRelease]) -> Result<(), Error> {
+    for release in releases {
+        let name = &release.package;
+        set_github_output(
+            &format!("{name}-released"),
+            &release.is_new().to_string(),
+        )?;
+        set_github_output(&format!("{name}-version"), &release.version)?;
+        set_github_output(&format!("{name}-tag"), &release.tag)?;
+    }
+    Ok(())
+}
+
+/// Set `<package>-released` to `false` for packages that were skipped
+/// without attempting a release, e.g. because the condition wasn't met
+/// or the package isn't listed in the `Release` trailer.
+fn write_skipped_outputs(names: &[String]) -> Result<(), Error> {
+    if env::var_os("GITHUB_OUTPUT").is_none() {
+        return Ok(());
+    }
+    for name in names {
+        set_github_output(&format!("{name}-released"), "false")?;
+    }
+    Ok(())
+}
+
+/// Format a markdown table summarizing the releases.
+fn format_summary(releases: &[PackageRelease]) -> String {
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+
+    let mut summary = String::from(
+        "| Package | Version | Tag | Published | Tagged |\n\
+         | ------- | ------- | --- | --------- | ------ |\n",
+    );
+    for release in releases {
+        summary.push_str(&format!(
+            "| {} | {} | `{}` | {} | {} |\n",
+            release.package,
+            release.version,
+            release.tag,
+            yes_no(release.published),
+            yes_no(release.tagged),
+        ));
+    }
+    summary
+}
+
+fn is_github_actions() -> bool {
+    env::var("GITHUB_ACTIONS").as_deref() == Ok("true")
+}
+
+/// Get the `Cargo.toml` (and the line within it) that an error relates
+/// to, if any.
+fn get_error_location(err: &Error) -> Option<(PathBuf, Option<usize>)> {
+    let Some(ReleasePackagesError::Package { package, cause }) =
+        err.downcast_ref()
+    else {
+        return None;
+    };
+    let package = Package::new(package);
+
+    match cause {
+        // The package isn't in the workspace, so point at the
+        // workspace's manifest.
+        ReleasePackageError::LocalVersion(
+            GetLocalVersionError::PackageNotFound(_),
+        ) => Some((package.workspace().join("Cargo.toml"), None)),
+        ReleasePackageError::Publish(_) => {
+            let manifest = package.get_manifest_path().ok()?;
+            let line = fs::read_to_string(&manifest)
+                .ok()
+                .and_then(|contents| find_version_line(&contents));
+            Some((manifest, line))
         }
+        _ => None,
     }
+}
 
-    let packages: Vec<_> = cli.package.iter().map(Package::new).collect();
+/// Find the line number (starting at 1) of the `version` key in the
+/// `[package]` table of a manifest.
+fn find_version_line(manifest: &str) -> Option<usize> {
+    let mut in_package = false;
+    for (index, line) in manifest.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+        } else if in_package
+            && line
+                .strip_prefix("version")
+                .is_some_and(|rest| rest.trim_start().starts_with(['=', '.']))
+        {
+            return Some(index + 1);
+        }
+    }
+    None
+}
 
-    Ok(release_packages(&packages)?)
+/// Make `path` relative to the root of the checkout, which is the form
+/// annotations expect.
+fn relative_to_workspace(path: &Path) -> &Path {
+    let root = env::var_os("GITHUB_WORKSPACE")
+        .map(PathBuf::from)
+        .or_else(|| env::current_dir().ok());
+    root.and_then(|root| path.strip_prefix(root).ok())
+        .unwrap_or(path)
 }
 
 fn main() {
     let cli = parse_args();
 
     if let Err(err) = execute(cli) {
-        println!("{err}");
+        let mut lines = vec![err.to_string()];
+        let mut cause = err.source();
+        while let Some(err) = cause {
+            lines.push(err.to_string());
+            cause = err.source();
+        }
+
+        println!("{}", lines[0]);
         println!("Caused by:");
-        let mut err = &*err;
-        while let Some(cause) = err.source() {
-            println!("    {cause}");
-            err = cause;
+        for line in &lines[1..] {
+            println!("    {line}");
+        }
+
+        if is_github_actions() {
+            let mut annotation = Annotation::error(lines.join("\n"))
+                .with_title("auto-release failed");
+            if let Some((file, line)) = get_error_location(&err) {
+                annotation =
+                    annotation.with_file(relative_to_workspace(&file), line);
+            }
+            annotation.emit();
         }
 
         process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_trailer_packages() {
+        let packages =
+            ["foo".to_string(), "bar".to_string(), "baz".to_string()];
+        let trailer = |key: &str, value: &str| Trailer {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+
+        assert_eq!(
+            select_trailer_packages(
+                &packages,
+                &[
+                    trailer("Signed-off-by", "foo"),
+                    trailer("Release", "baz, qux,"),
+                    trailer("release", "foo"),
+                ]
+            ),
+            (
+                vec!["foo".to_string(), "baz".to_string()],
+                vec!["qux".to_string()]
+            )
+        );
+        assert_eq!(
+            select_trailer_packages(&packages, &[]),
+            (Vec::new(), Vec::new())
+        );
+    }
+
+    #[test]
+    fn test_find_version_line() {
+        let manifest = "\
+[workspace]
+version = \"0.0.0\"
+
+[package]
+name = \"foo\"
+version = \"1.2.3\"
+";
+        assert_eq!(find_version_line(manifest), Some(6));
+        assert_eq!(find_version_line("[package]\nname = \"foo\"\n"), None);
+        assert_eq!(
+            find_version_line("[package]\nversion.workspace = true\n"),
+            Some(2)
+        );
+    }
+}
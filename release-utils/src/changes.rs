@@ -0,0 +1,247 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Detect which packages have changed since their last release.
+
+use crate::cmd::RunCommandError;
+use crate::package::{WorkspacePackage, get_workspace_packages};
+use crate::{Package, Repo};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// Changes to a package since its last release.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct PackageChanges {
+    /// Name of the package.
+    pub package: String,
+
+    /// Tag of the last release, or `None` if the package has never
+    /// been released.
+    pub last_tag: Option<String>,
+
+    /// Files in the package's directory that changed since the last
+    /// release. Paths are relative to the root of the repo. Files in
+    /// the directories of other packages nested inside this one are
+    /// not included.
+    pub changed_files: Vec<PathBuf>,
+
+    /// Path dependencies of the package (direct or indirect) with
+    /// changes since the package's last release. This is only filled
+    /// in if path dependency propagation is enabled.
+    pub changed_deps: Vec<String>,
+}
+
+impl PackageChanges {
+    /// Whether the package has anything to release. This is true if
+    /// the package has never been released.
+    pub fn has_changes(&self) -> bool {
+        self.last_tag.is_none()
+            || !self.changed_files.is_empty()
+            || !self.changed_deps.is_empty()
+    }
+}
+
+/// Error returned by [`get_package_changes`].
+#[derive(Debug)]
+pub enum GetPackageChangesError {
+    /// Failed to get the cargo metadata.
+    Metadata(RunCommandError),
+
+    /// Package not found in the cargo metadata.
+    PackageNotFound(String),
+
+    /// A package's manifest is not inside the repo.
+    ManifestOutsideRepo(PathBuf),
+
+    /// A git error occurred.
+    Git(RunCommandError),
+}
+
+impl Display for GetPackageChangesError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Metadata(_) => write!(f, "failed to get cargo metadata"),
+            Self::PackageNotFound(pkg) => {
+                write!(f, "package {pkg} not found in cargo metadata")
+            }
+            Self::ManifestOutsideRepo(path) => {
+                write!(f, "{} is not in the git repo", path.display())
+            }
+            Self::Git(_) => write!(f, "git error"),
+        }
+    }
+}
+
+impl std::error::Error for GetPackageChangesError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Metadata(err) | Self::Git(err) => Some(err),
+            Self::PackageNotFound(_) | Self::ManifestOutsideRepo(_) => None,
+        }
+    }
+}
+
+/// Get the changes to each of `packages` between its last release and
+/// `rev` (e.g. `HEAD`).
+///
+/// The last release is the most recent tag reachable from `rev` that
+/// matches the package's tag format, see [`Package::get_git_tag_name`].
+/// Changed files are assigned to the package whose directory most
+/// closely contains them.
+///
+/// If `propagate_path_deps` is true, changes to a package's path
+/// dependencies in the workspace (found through the cargo metadata)
+/// are also reported, see [`PackageChanges::changed_deps`].
+pub fn get_package_changes(
+    repo: &Repo,
+    packages: &[Package],
+    rev: &str,
+    propagate_path_deps: bool,
+) -> Result<Vec<PackageChanges>, GetPackageChangesError> {
+    // Packages in each workspace, keyed by the workspace path.
+    let mut workspaces: BTreeMap<&Path, Workspace> = BTreeMap::new();
+
+    let mut all_changes = Vec::new();
+    for package in packages {
+        let workspace = match workspaces.get(package.workspace()) {
+            Some(workspace) => workspace,
+            None => {
+                let workspace = Workspace::load(repo, package.workspace())?;
+                workspaces.entry(package.workspace()).or_insert(workspace)
+            }
+        };
+        if !workspace.dirs.contains_key(package.name()) {
+            return Err(GetPackageChangesError::PackageNotFound(
+                package.name().to_string(),
+            ));
+        }
+
+        let last_tag = repo
+            .get_last_tag_matching(&package.get_git_tag_name("*"), rev)
+            .map_err(GetPackageChangesError::Git)?;
+        let Some(last_tag) = last_tag else {
+            all_changes.push(PackageChanges {
+                package: package.name().to_string(),
+                last_tag: None,
+                changed_files: Vec::new(),
+                changed_deps: Vec::new(),
+            });
+            continue;
+        };
+
+        let files = repo
+            .get_changed_files(&last_tag, rev)
+            .map_err(GetPackageChangesError::Git)?;
+
+        let mut changed_files = Vec::new();
+        let mut changed_packages = BTreeSet::new();
+        for file in files {
+            if let Some(owner) = workspace.get_owner(&file) {
+                if owner == package.name() {
+                    changed_files.push(file);
+                } else {
+                    changed_packages.insert(owner.to_string());
+                }
+            }
+        }
+
+        let changed_deps = if propagate_path_deps {
+            workspace
+                .get_transitive_deps(package.name())
+                .into_iter()
+                .filter(|dep| changed_packages.contains(dep))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        all_changes.push(PackageChanges {
+            package: package.name().to_string(),
+            last_tag: Some(last_tag),
+            changed_files,
+            changed_deps,
+        });
+    }
+
+    Ok(all_changes)
+}
+
+/// Layout of the packages in a workspace.
+struct Workspace {
+    /// Package directories relative to the repo root, keyed by package
+    /// name.
+    dirs: BTreeMap<String, PathBuf>,
+
+    /// Path dependencies, keyed by package name.
+    path_deps: BTreeMap<String, Vec<String>>,
+}
+
+impl Workspace {
+    fn load(
+        repo: &Repo,
+        workspace: &Path,
+    ) -> Result<Self, GetPackageChangesError> {
+        let packages = get_workspace_packages(workspace)
+            .map_err(GetPackageChangesError::Metadata)?;
+
+        let mut dirs = BTreeMap::new();
+        let mut path_deps = BTreeMap::new();
+        for WorkspacePackage {
+            name,
+            manifest_path,
+            path_deps: deps,
+        } in packages
+        {
+            let dir = get_package_dir(repo, &manifest_path).ok_or(
+                GetPackageChangesError::ManifestOutsideRepo(manifest_path),
+            )?;
+            dirs.insert(name.clone(), dir);
+            path_deps.insert(name, deps);
+        }
+        Ok(Self { dirs, path_deps })
+    }
+
+    /// Get the package whose directory most closely contains `file`.
+    fn get_owner(&self, file: &Path) -> Option<&str> {
+        self.dirs
+            .iter()
+            .filter(|(_, dir)| file.starts_with(dir))
+            .max_by_key(|(_, dir)| dir.components().count())
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Get the direct and indirect path dependencies of `package`
+    /// within the workspace.
+    fn get_transitive_deps(&self, package: &str) -> BTreeSet<String> {
+        let mut deps = BTreeSet::new();
+        let mut queue = vec![package];
+        while let Some(name) = queue.pop() {
+            for dep in self.path_deps.get(name).into_iter().flatten() {
+                if dep != package
+                    && self.dirs.contains_key(dep)
+                    && deps.insert(dep.clone())
+                {
+                    queue.push(dep);
+                }
+            }
+        }
+        deps
+    }
+}
+
+/// Get the directory of a package relative to the repo root.
+fn get_package_dir(repo: &Repo, manifest_path: &Path) -> Option<PathBuf> {
+    let dir = manifest_path.parent()?;
+    if let Ok(dir) = dir.strip_prefix(repo.path()) {
+        return Some(dir.to_path_buf());
+    }
+    // The repo path is canonical, but the metadata path may not be.
+    let dir = dir.canonicalize().ok()?;
+    dir.strip_prefix(repo.path()).ok().map(Path::to_path_buf)
+}
@@ -9,9 +9,10 @@
 //! Utilities for running child processes.
 
 use std::fmt::{self, Display, Formatter};
-use std::io;
-use std::process::{Child, Command, ExitStatus};
+use std::io::{self, Write};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
 use std::string::FromUtf8Error;
+use std::thread;
 
 /// Error returned when running a child process fails.
 #[derive(Debug)]
@@ -129,6 +130,20 @@ pub fn get_cmd_stdout(mut cmd: Command) -> Result<Vec<u8>, RunCommandError> {
     }
 }
 
+/// Log a command, run it, and get its output.
+///
+/// Unlike [`get_cmd_stdout`], a non-zero exit code is not treated as an
+/// error. Both stdout and stderr are captured, and the caller is
+/// responsible for checking the exit status.
+///
+/// Returns an error if the process fails to launch.
+pub fn get_cmd_output(mut cmd: Command) -> Result<Output, RunCommandError> {
+    let cmd_str = format_cmd(&cmd);
+    println!("Running: {cmd_str}");
+    cmd.output()
+        .map_err(|err| RunCommandError::Launch { cmd: cmd_str, err })
+}
+
 /// Log a command, run it, and get its output as a `String`.
 ///
 /// Returns an error if the process fails to launch, or if the exit code
@@ -140,6 +155,47 @@ pub fn get_cmd_stdout_utf8(cmd: Command) -> Result<String, RunCommandError> {
         .map_err(|err| RunCommandError::NonUtf8 { cmd: cmd_str, err })
 }
 
+/// Run a command with `input` written to its stdin, and get its stdout
+/// as a `String`.
+pub(crate) fn run_with_input(
+    mut cmd: Command,
+    input: &[u8],
+) -> Result<String, RunCommandError> {
+    let cmd_str = format_cmd(&cmd);
+    cmd.stdin(Stdio::piped());
+    cmd.stdout(Stdio::piped());
+    let mut child = cmd.spawn().map_err(|err| RunCommandError::Launch {
+        cmd: cmd_str.clone(),
+        err,
+    })?;
+
+    // Write the input from a separate thread, otherwise the child could
+    // block on writing to stdout while we block on writing to stdin.
+    // OK to unwrap, we know stdin is set.
+    let mut stdin = child.stdin.take().unwrap();
+    let output = thread::scope(|s| {
+        s.spawn(move || {
+            // Ignore write errors; if the child exits early that will be
+            // reported by its exit status.
+            let _ = stdin.write_all(input);
+        });
+        child.wait_with_output()
+    })
+    .map_err(|err| RunCommandError::Wait {
+        cmd: cmd_str.clone(),
+        err,
+    })?;
+
+    if !output.status.success() {
+        return Err(RunCommandError::NonZeroExit {
+            cmd: cmd_str,
+            status: output.status,
+        });
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|err| RunCommandError::NonUtf8 { cmd: cmd_str, err })
+}
+
 /// Wait for a child process to exit.
 ///
 /// Returns an error if waiting fails, or if the exit code is non-zero.
@@ -6,12 +6,15 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::cmd::{
-    RunCommandError, format_cmd, get_cmd_stdout_utf8, wait_for_child,
-};
+use crate::HttpConfig;
+use crate::cmd::{RunCommandError, get_cmd_output, run_with_input};
+use crate::http::parse_retry_after;
+use std::collections::BTreeMap;
 use std::fmt::{self, Display, Formatter};
-use std::io::Read;
-use std::process::{Child, Command, Stdio};
+use std::io;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, SystemTime};
 
 /// Error returned by [`CrateRegistry::get_crate_versions`].
 #[derive(Debug)]
@@ -19,14 +22,78 @@ pub enum GetCrateVersionsError {
     /// The crate has not yet been published.
     NotPublished,
 
-    /// An internal error occurred.
-    Internal {
-        /// Description of the internal error.
+    /// The registry rejected the request with HTTP 401 or 403. This
+    /// may happen with private registries that require authentication.
+    Unauthorized,
+
+    /// The registry rejected the request with HTTP 429, and retrying
+    /// would exceed [`HttpConfig::retry_budget`].
+    RateLimited {
+        /// Delay requested by the registry's `Retry-After` header, if
+        /// present.
+        retry_after: Option<Duration>,
+    },
+
+    /// The registry responded with an unexpected HTTP status code.
+    Http {
+        /// HTTP status code.
+        code: u16,
+    },
+
+    /// The request failed without an HTTP response, for example due to
+    /// a DNS, connection, TLS, or timeout error.
+    Network {
+        /// Error message from `curl`.
         msg: String,
+    },
 
-        /// Optional underlying error.
-        cause: Option<Box<dyn std::error::Error + Send + Sync + 'static>>,
+    /// A line of the crate's index file could not be parsed.
+    Parse {
+        /// Line number (starting at 1) within the index file.
+        line: usize,
     },
+
+    /// A required program (`curl` or `jq`) is not installed.
+    ToolMissing {
+        /// Name of the program.
+        tool: String,
+    },
+
+    /// A child process failed in some other way.
+    Process(RunCommandError),
+}
+
+impl GetCrateVersionsError {
+    /// Whether the error is likely to be temporary, such that retrying
+    /// the request later might succeed.
+    ///
+    /// This is true for network errors, rate limiting, and HTTP 5xx
+    /// server errors.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Network { .. } | Self::RateLimited { .. } => true,
+            Self::Http { code } => *code >= 500,
+            Self::NotPublished
+            | Self::Unauthorized
+            | Self::Parse { .. }
+            | Self::ToolMissing { .. }
+            | Self::Process(_) => false,
+        }
+    }
+
+    /// Convert a process error, detecting if the program is missing.
+    fn from_process(tool: &str, err: RunCommandError) -> Self {
+        match err {
+            RunCommandError::Launch { err, .. }
+                if err.kind() == io::ErrorKind::NotFound =>
+            {
+                Self::ToolMissing {
+                    tool: tool.to_string(),
+                }
+            }
+            err => Self::Process(err),
+        }
+    }
 }
 
 impl Display for GetCrateVersionsError {
@@ -34,9 +101,21 @@ impl Display for GetCrateVersionsError {
         write!(f, "failed to get crate versions: ")?;
         match self {
             Self::NotPublished => write!(f, "crate has not yet been published"),
-            Self::Internal { msg, .. } => {
-                write!(f, "{msg}")
+            Self::Unauthorized => {
+                write!(f, "the registry rejected the request as unauthorized")
+            }
+            Self::RateLimited { .. } => {
+                write!(f, "rate limited by the registry (HTTP 429)")
+            }
+            Self::Http { code } => write!(f, "invalid HTTP code: {code}"),
+            Self::Network { msg } => write!(f, "network error: {msg}"),
+            Self::Parse { line } => {
+                write!(f, "failed to parse line {line} of the index file")
+            }
+            Self::ToolMissing { tool } => {
+                write!(f, "\"{tool}\" is not installed")
             }
+            Self::Process(_) => write!(f, "child process failed"),
         }
     }
 }
@@ -44,13 +123,8 @@ impl Display for GetCrateVersionsError {
 impl std::error::Error for GetCrateVersionsError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            Self::NotPublished => None,
-            Self::Internal { cause, .. } => cause.as_ref().map(|err| {
-                // TODO: for some reason this extra cast is needed to
-                // drop the Send/Sync bounds.
-                let err: &(dyn std::error::Error + 'static) = &**err;
-                err
-            }),
+            Self::Process(err) => Some(err),
+            _ => None,
         }
     }
 }
@@ -59,6 +133,9 @@ impl std::error::Error for GetCrateVersionsError {
 pub struct CrateRegistry {
     /// Base URL of the sparse registry.
     pub registry_url: String,
+
+    /// Network settings for requests to the registry.
+    pub http: HttpConfig,
 }
 
 impl CrateRegistry {
@@ -66,9 +143,24 @@ impl CrateRegistry {
     pub const DEFAULT_REGISTRY: &'static str = "https://index.crates.io";
 
     /// Create a new `CrateRegistry` with the default registry.
+    ///
+    /// Network settings are loaded from Cargo's configuration, see
+    /// [`HttpConfig::from_cargo_config`]. If the configuration cannot
+    /// be loaded, a warning is printed and default settings are used.
     pub fn new() -> Self {
+        let http = HttpConfig::from_cargo_config().unwrap_or_else(|err| {
+            println!("warning: {err}");
+            HttpConfig::default()
+        });
+        Self::with_http_config(http)
+    }
+
+    /// Create a new `CrateRegistry` with the default registry and the
+    /// given network settings.
+    pub fn with_http_config(http: HttpConfig) -> Self {
         Self {
             registry_url: Self::DEFAULT_REGISTRY.to_string(),
+            http,
         }
     }
 
@@ -104,65 +196,250 @@ impl CrateRegistry {
     ///
     /// If the crate has not yet been published,
     /// [`GetCrateVersionsError::NotPublished`] is returned.
+    ///
+    /// If the registry responds with HTTP 429 (Too Many Requests), the
+    /// request is retried after the delay given by the `Retry-After`
+    /// header, as long as the total delay stays within
+    /// [`HttpConfig::retry_budget`].
     pub fn get_crate_versions(
         &self,
         crate_name: &str,
     ) -> Result<Vec<String>, GetCrateVersionsError> {
-        let (mut curl_proc, curl_cmd_str) = spawn_curl(self, crate_name)
-            .map_err(|err| GetCrateVersionsError::Internal {
-                msg: "failed to launch curl".to_string(),
-                cause: Some(Box::new(err)),
-            })?;
-
-        // OK to unwrap, we know stderr and stdout are set.
-        let mut curl_stderr_pipe = curl_proc.stderr.take().unwrap();
-        let curl_stdout_pipe = curl_proc.stdout.take().unwrap();
-
-        let versions_result = parse_versions_from_crate_json(curl_stdout_pipe);
-
-        wait_for_child(curl_proc, curl_cmd_str).map_err(|err| {
-            GetCrateVersionsError::Internal {
-                msg: "curl failed".to_string(),
-                cause: Some(Box::new(err)),
+        let mut results = self.get_many_crate_versions(&[crate_name])?;
+        // OK to unwrap, there is a result for every requested crate.
+        results.remove(crate_name).unwrap()
+    }
+
+    /// Get all published versions of several crates.
+    ///
+    /// All of the index files are fetched by a single `curl` process,
+    /// reusing the connection to the registry, and parsed by a single
+    /// `jq` process. For large workspaces this is much faster than
+    /// calling [`CrateRegistry::get_crate_versions`] for each crate.
+    ///
+    /// The returned map has an entry for every crate in `crate_names`,
+    /// with the same semantics as the result of `get_crate_versions`.
+    /// The outer error is returned if the lookup could not be done at
+    /// all, for example because `curl` is not installed.
+    #[allow(clippy::type_complexity)]
+    pub fn get_many_crate_versions(
+        &self,
+        crate_names: &[&str],
+    ) -> Result<
+        BTreeMap<String, Result<Vec<String>, GetCrateVersionsError>>,
+        GetCrateVersionsError,
+    > {
+        let entries = self.get_many_index_entries(crate_names)?;
+        Ok(entries
+            .into_iter()
+            .map(|(crate_name, entries)| {
+                let versions = entries.map(|entries| {
+                    entries.into_iter().map(|entry| entry.vers).collect()
+                });
+                (crate_name, versions)
+            })
+            .collect())
+    }
+
+    /// Get the SHA-256 checksum of the `.crate` file of a published
+    /// version of a crate, as lowercase hex.
+    ///
+    /// The checksum is read from the registry index. `None` is returned
+    /// if the version hasn't been published, or the index doesn't have
+    /// a checksum for it.
+    pub fn get_crate_checksum(
+        &self,
+        crate_name: &str,
+        version: &str,
+    ) -> Result<Option<String>, GetCrateVersionsError> {
+        let mut results = self.get_many_index_entries(&[crate_name])?;
+        // OK to unwrap, there is a result for every requested crate.
+        let entries = results.remove(crate_name).unwrap()?;
+        Ok(entries
+            .into_iter()
+            .find(|entry| entry.vers == version)
+            .and_then(|entry| entry.cksum))
+    }
+
+    /// Get the index entries of several crates, see
+    /// [`CrateRegistry::get_many_crate_versions`].
+    #[allow(clippy::type_complexity)]
+    fn get_many_index_entries(
+        &self,
+        crate_names: &[&str],
+    ) -> Result<
+        BTreeMap<String, Result<Vec<IndexEntry>, GetCrateVersionsError>>,
+        GetCrateVersionsError,
+    > {
+        let mut results = BTreeMap::new();
+        let mut pending: Vec<&str> = crate_names.to_vec();
+        pending.sort_unstable();
+        pending.dedup();
+
+        let mut total_wait = Duration::ZERO;
+        while !pending.is_empty() {
+            let responses = self.fetch_index_files(&pending)?;
+
+            let mut rate_limited = Vec::new();
+            let mut max_retry_after = None;
+            let mut bodies = Vec::new();
+            for (crate_name, response) in pending.iter().zip(responses) {
+                match response {
+                    IndexResponse::Body(body) => {
+                        bodies.push((*crate_name, body))
+                    }
+                    IndexResponse::RateLimited { retry_after } => {
+                        rate_limited.push(*crate_name);
+                        max_retry_after = max_retry_after.max(retry_after);
+                    }
+                    IndexResponse::Error(err) => {
+                        results.insert(crate_name.to_string(), Err(err));
+                    }
+                }
             }
-        })?;
-
-        let mut stderr_bytes = Vec::new();
-        curl_stderr_pipe
-            .read_to_end(&mut stderr_bytes)
-            .map_err(|err| GetCrateVersionsError::Internal {
-                msg: "failed to read http code from curl".to_string(),
-                cause: Some(Box::new(err)),
-            })?;
-
-        let stderr = String::from_utf8(stderr_bytes).map_err(|err| {
-            GetCrateVersionsError::Internal {
-                msg: "curl http code is not utf-8".to_string(),
-                cause: Some(Box::new(err)),
+
+            let inputs: Vec<_> = bodies.iter().map(|(_, b)| &b[..]).collect();
+            let entries = parse_index_files(&inputs)?;
+            for ((crate_name, _), entries) in bodies.iter().zip(entries) {
+                results.insert(crate_name.to_string(), entries);
+            }
+
+            if rate_limited.is_empty() {
+                break;
             }
-        })?;
 
-        let code: i32 = stderr.trim().parse().map_err(|_| {
-            GetCrateVersionsError::Internal {
-                msg: format!("invalid HTTP code: {stderr:?}"),
-                cause: None,
+            // Wait at least one second, even if the server didn't say
+            // how long to wait.
+            let wait = max_retry_after
+                .unwrap_or_default()
+                .max(Duration::from_secs(1));
+            total_wait += wait;
+            if total_wait > self.http.retry_budget {
+                for crate_name in rate_limited {
+                    results.insert(
+                        crate_name.to_string(),
+                        Err(GetCrateVersionsError::RateLimited {
+                            retry_after: max_retry_after,
+                        }),
+                    );
+                }
+                break;
             }
-        })?;
-        if code == 404 {
-            return Err(GetCrateVersionsError::NotPublished);
+
+            println!(
+                "rate limited by the registry, retrying in {} seconds",
+                wait.as_secs()
+            );
+            thread::sleep(wait);
+            pending = rate_limited;
         }
-        if code != 200 {
-            return Err(GetCrateVersionsError::Internal {
-                msg: format!("invalid HTTP code: {code}"),
-                cause: None,
-            });
+
+        Ok(results)
+    }
+
+    /// Fetch the index files of crates with a single `curl` process.
+    ///
+    /// The returned responses are in the same order as `crate_names`.
+    fn fetch_index_files(
+        &self,
+        crate_names: &[&str],
+    ) -> Result<Vec<IndexResponse>, GetCrateVersionsError> {
+        let mut cmd = Command::new("curl");
+        // Turn off progress output.
+        cmd.args(["--silent"]);
+        // After each transfer, write a marker line with the HTTP status
+        // code, the Retry-After header (if any), and the error message
+        // (if any). This separates the response bodies, which are all
+        // written to stdout.
+        cmd.arg("--write-out");
+        cmd.arg(format!(
+            "\\n{TRANSFER_END_MARKER}%{{http_code}}\\t%header{{retry-after}}\\t%{{errormsg}}\\n"
+        ));
+        // Apply proxy, CA bundle, and timeout settings.
+        self.http.apply_to_curl(&mut cmd);
+        // Fetch each crate's JSON file from the index.
+        for crate_name in crate_names {
+            cmd.arg(self.get_crate_url(crate_name));
         }
 
-        versions_result.map_err(|err| GetCrateVersionsError::Internal {
-            msg: "jq failed".to_string(),
-            cause: Some(Box::new(err)),
-        })
+        let output = get_cmd_output(cmd)
+            .map_err(|err| GetCrateVersionsError::from_process("curl", err))?;
+
+        let mut responses = parse_curl_transfers(&output.stdout);
+
+        // If curl stopped early there are fewer transfers than crates.
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let msg = match stderr.trim() {
+            "" => "no response".to_string(),
+            msg => msg.to_string(),
+        };
+        while responses.len() < crate_names.len() {
+            responses.push(IndexResponse::Error(
+                GetCrateVersionsError::Network { msg: msg.clone() },
+            ));
+        }
+
+        Ok(responses)
+    }
+}
+
+/// Marker written by `curl` after each transfer, see
+/// [`CrateRegistry::fetch_index_files`].
+const TRANSFER_END_MARKER: &str = "--release-utils-transfer-end--";
+
+/// Response to a request for a crate's index file.
+enum IndexResponse {
+    Body(Vec<u8>),
+    RateLimited { retry_after: Option<Duration> },
+    Error(GetCrateVersionsError),
+}
+
+/// Split the output of `curl` into per-transfer responses.
+fn parse_curl_transfers(mut output: &[u8]) -> Vec<IndexResponse> {
+    let marker = format!("\n{TRANSFER_END_MARKER}");
+    let marker = marker.as_bytes();
+
+    let mut responses = Vec::new();
+    while let Some(pos) = output.windows(marker.len()).position(|w| w == marker)
+    {
+        let body = &output[..pos];
+        let rest = &output[pos + marker.len()..];
+        let line_len =
+            rest.iter().position(|b| *b == b'\n').unwrap_or(rest.len());
+        let status = String::from_utf8_lossy(&rest[..line_len]);
+        output = rest.get(line_len + 1..).unwrap_or_default();
+
+        let mut status = status.splitn(3, '\t');
+        let code = status.next().unwrap_or_default();
+        let retry_after = status.next().unwrap_or_default();
+        let errormsg = status.next().unwrap_or_default().trim();
+
+        // curl uses "000" if no response was received. A transfer that
+        // failed after the status line (e.g. hitting `--max-time`)
+        // keeps the status code, but has an error message, and the
+        // body may be truncated.
+        let code: u16 = code.trim().parse().unwrap_or_default();
+        if code == 0 || !errormsg.is_empty() {
+            responses.push(IndexResponse::Error(
+                GetCrateVersionsError::Network {
+                    msg: errormsg.to_string(),
+                },
+            ));
+            continue;
+        }
+        responses.push(match code {
+            200 => IndexResponse::Body(body.to_vec()),
+            401 | 403 => {
+                IndexResponse::Error(GetCrateVersionsError::Unauthorized)
+            }
+            404 => IndexResponse::Error(GetCrateVersionsError::NotPublished),
+            429 => IndexResponse::RateLimited {
+                retry_after: parse_retry_after(retry_after, SystemTime::now()),
+            },
+            code => IndexResponse::Error(GetCrateVersionsError::Http { code }),
+        });
     }
+    responses
 }
 
 impl Default for CrateRegistry {
@@ -171,46 +448,91 @@ impl Default for CrateRegistry {
     }
 }
 
-fn spawn_curl(
-    registry: &CrateRegistry,
-    crate_name: &str,
-) -> Result<(Child, String), RunCommandError> {
-    let mut cmd = Command::new("curl");
-    // Turn off progress output.
-    cmd.args(["--silent"]);
-    // Write the HTTP status code to stderr.
-    cmd.args(["--write-out", "%{stderr}%{http_code}"]);
-    // Fetch the crate's JSON file from the index.
-    cmd.arg(registry.get_crate_url(crate_name));
-    // Capture both stdout and stderr.
-    cmd.stderr(Stdio::piped());
-    cmd.stdout(Stdio::piped());
-    let curl_cmd_str = format_cmd(&cmd);
-    let child = cmd.spawn().map_err(|err| RunCommandError::Launch {
-        cmd: curl_cmd_str.clone(),
-        err,
-    })?;
-    Ok((child, curl_cmd_str))
+/// One line of a crate's index file.
+#[derive(Debug, PartialEq)]
+struct IndexEntry {
+    /// Version of the crate.
+    vers: String,
+
+    /// SHA-256 checksum of the `.crate` file.
+    cksum: Option<String>,
 }
 
-fn parse_versions_from_crate_json(
-    input: impl Into<Stdio>,
-) -> Result<Vec<String>, RunCommandError> {
+/// Parse crates' index files.
+///
+/// Each line of an index file is a JSON object describing one version of
+/// the crate. All of the files are parsed by a single `jq` process. The
+/// returned results are in the same order as `inputs`.
+fn parse_index_files(
+    inputs: &[&[u8]],
+) -> Result<
+    Vec<Result<Vec<IndexEntry>, GetCrateVersionsError>>,
+    GetCrateVersionsError,
+> {
+    if inputs.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Concatenate the files, making sure each ends in a newline so that
+    // lines can be mapped back to the file they came from.
+    let mut combined = Vec::new();
+    let mut line_counts = Vec::new();
+    for input in inputs {
+        let start = combined.len();
+        combined.extend_from_slice(input);
+        if !input.is_empty() && !input.ends_with(b"\n") {
+            combined.push(b'\n');
+        }
+        line_counts
+            .push(combined[start..].iter().filter(|b| **b == b'\n').count());
+    }
+
     let mut cmd = Command::new("jq");
+    // Read each line as a string rather than as JSON, so that parse
+    // errors can be reported per line.
+    cmd.arg("--raw-input");
     // Remove quotes.
     cmd.arg("--raw-output");
-    // Select the version field.
-    cmd.arg(".vers");
-    cmd.stdin(input);
-    let output = get_cmd_stdout_utf8(cmd)?;
-
-    Ok(output.lines().map(|l| l.to_string()).collect())
+    // Select the version and checksum fields separated by a tab, or
+    // output null if the line is invalid. The checksum may be empty.
+    cmd.arg(
+        r#"(try fromjson catch null)
+           | if (.vers? | type) == "string"
+             then .vers + "\t"
+                  + (if (.cksum | type) == "string" then .cksum else "" end)
+             else null end"#,
+    );
+    let output = run_with_input(cmd, &combined)
+        .map_err(|err| GetCrateVersionsError::from_process("jq", err))?;
+
+    let mut lines = output.lines();
+    Ok(line_counts
+        .into_iter()
+        .map(|count| {
+            // Take all of the file's lines up front; collecting into a
+            // `Result` stops early on error.
+            let file_lines: Vec<_> = lines.by_ref().take(count).collect();
+            file_lines
+                .into_iter()
+                .enumerate()
+                .map(|(index, line)| {
+                    let (vers, cksum) = line.split_once('\t').ok_or(
+                        GetCrateVersionsError::Parse { line: index + 1 },
+                    )?;
+                    Ok(IndexEntry {
+                        vers: vers.to_string(),
+                        cksum: (!cksum.is_empty()).then(|| cksum.to_string()),
+                    })
+                })
+                .collect()
+        })
+        .collect())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::{self, File};
+    use std::fs;
     use tempfile::tempdir;
 
     #[test]
@@ -232,6 +554,24 @@ mod tests {
         );
     }
 
+    /// Parse index files, keeping only the versions.
+    #[allow(clippy::type_complexity)]
+    fn parse_versions(
+        inputs: &[&[u8]],
+    ) -> Result<
+        Vec<Result<Vec<String>, GetCrateVersionsError>>,
+        GetCrateVersionsError,
+    > {
+        Ok(parse_index_files(inputs)?
+            .into_iter()
+            .map(|entries| {
+                entries.map(|entries| {
+                    entries.into_iter().map(|entry| entry.vers).collect()
+                })
+            })
+            .collect())
+    }
+
     #[test]
     fn test_jq() {
         let tmp_dir = tempdir().unwrap();
@@ -241,8 +581,84 @@ mod tests {
 {"name":"release-utils","vers":"0.4.0","deps":[{"name":"anyhow","req":"^1.0.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal"},{"name":"cargo_metadata","req":"^0.18.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal"},{"name":"crates-index","req":"^2.3.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal"},{"name":"tempfile","req":"^3.0.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"dev"},{"name":"ureq","req":"^2.8.0","features":["http-interop"],"optional":false,"default_features":true,"target":null,"kind":"normal"}],"cksum":"0aa93a5aaaed004e0222a3207cf5ec5dc15a39baea0e412bebfb7aa7bb8fa14c","features":{},"yanked":false,"rust_version":"1.70"}
 {"name":"release-utils","vers":"0.4.1","deps":[{"name":"anyhow","req":"^1.0.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal"},{"name":"cargo_metadata","req":"^0.18.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal"},{"name":"crates-index","req":"^2.3.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal"},{"name":"tempfile","req":"^3.0.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"dev"},{"name":"ureq","req":"^2.8.0","features":["http-interop"],"optional":false,"default_features":true,"target":null,"kind":"normal"}],"cksum":"02922e087d9f1da9f783ca54f4621f1a156ffc3f8563d66c2d74b5d2d6363ccf","features":{},"yanked":false,"rust_version":"1.70"}
 "#).unwrap();
-        let file = File::open(path).unwrap();
-        let versions = parse_versions_from_crate_json(file).unwrap();
-        assert_eq!(versions, ["0.2.4", "0.3.0", "0.4.0", "0.4.1"]);
+        let input = fs::read(path).unwrap();
+        let versions = parse_versions(&[&input]).unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(
+            versions[0].as_ref().unwrap(),
+            &["0.2.4", "0.3.0", "0.4.0", "0.4.1"]
+        );
+        let entries = parse_index_files(&[&input]).unwrap();
+        assert_eq!(
+            entries[0].as_ref().unwrap()[3],
+            IndexEntry {
+                vers: "0.4.1".to_string(),
+                cksum: Some(
+                    "02922e087d9f1da9f783ca54f4621f1a156ffc3f8563d66c2d74b5d2d6363ccf"
+                        .to_string()
+                ),
+            }
+        );
+
+        // Multiple files, one without a trailing newline.
+        let versions = parse_versions(&[
+            b"{\"vers\":\"1.0.0\"}\n{\"vers\":\"1.0.1\"}",
+            b"",
+            b"{\"vers\":\"0.1.0\"}\n",
+        ])
+        .unwrap();
+        assert_eq!(versions[0].as_ref().unwrap(), &["1.0.0", "1.0.1"]);
+        assert!(versions[1].as_ref().unwrap().is_empty());
+        assert_eq!(versions[2].as_ref().unwrap(), &["0.1.0"]);
+
+        // The checksum is optional.
+        let entries = parse_index_files(&[b"{\"vers\":\"1.0.0\"}\n"]).unwrap();
+        assert_eq!(entries[0].as_ref().unwrap()[0].cksum, None);
+
+        let versions = parse_versions(&[
+            b"{\"vers\":\"1.0.0\"}\nnot json\n{\"vers\":\"1.0.1\"}\n",
+            b"{\"vers\":\"1.0.0\"}\n{\"name\":\"foo\"}\n",
+            b"{\"vers\":\"1.0.0\"}\n",
+        ])
+        .unwrap();
+        assert!(matches!(
+            versions[0],
+            Err(GetCrateVersionsError::Parse { line: 2 })
+        ));
+        assert!(matches!(
+            versions[1],
+            Err(GetCrateVersionsError::Parse { line: 2 })
+        ));
+        assert_eq!(versions[2].as_ref().unwrap(), &["1.0.0"]);
+    }
+
+    #[test]
+    fn test_parse_curl_transfers() {
+        let output = format!(
+            "{{\"vers\":\"1.0.0\"}}\n\n{m}200\t\t\nnot found\n{m}404\t\t\n\n{m}429\t7\t\n\n{m}000\t\tCould not resolve host\n{{\"vers\"\n{m}200\t\tOperation timed out\n",
+            m = TRANSFER_END_MARKER
+        );
+        let responses = parse_curl_transfers(output.as_bytes());
+        assert_eq!(responses.len(), 5);
+        assert!(
+            matches!(&responses[0], IndexResponse::Body(b) if b == b"{\"vers\":\"1.0.0\"}\n")
+        );
+        assert!(matches!(
+            responses[1],
+            IndexResponse::Error(GetCrateVersionsError::NotPublished)
+        ));
+        assert!(matches!(
+            responses[2],
+            IndexResponse::RateLimited { retry_after: Some(d) } if d == Duration::from_secs(7)
+        ));
+        assert!(matches!(
+            &responses[3],
+            IndexResponse::Error(GetCrateVersionsError::Network { msg }) if msg == "Could not resolve host"
+        ));
+        // Truncated body after a 200 status.
+        assert!(matches!(
+            &responses[4],
+            IndexResponse::Error(GetCrateVersionsError::Network { msg }) if msg == "Operation timed out"
+        ));
     }
 }
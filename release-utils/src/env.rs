@@ -6,8 +6,19 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+//! Utilities for reading information from the CI environment.
+
+use crate::cmd::{RunCommandError, get_cmd_stdout_utf8};
+use std::collections::BTreeMap;
+use std::collections::hash_map::RandomState;
 use std::env;
 use std::fmt::{self, Display, Formatter};
+use std::fs::OpenOptions;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
 
 /// Error getting an environment variable
 ///
@@ -48,3 +59,1425 @@ pub fn get_github_sha() -> Result<String, VarError> {
         err,
     })
 }
+
+/// Kind of event that triggered a CI job.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum CiEventKind {
+    /// Commits or tags were pushed.
+    Push,
+
+    /// A pull request (or merge request) was opened or updated.
+    PullRequest,
+
+    /// The job is validating a merge queue (or merge train) entry.
+    MergeQueue,
+
+    /// The job was triggered on a schedule.
+    Schedule,
+
+    /// The job was triggered manually.
+    Manual,
+
+    /// A release was created.
+    Release,
+
+    /// Some other event. Contains the CI system's name for the event.
+    Other(String),
+}
+
+/// Information about the CI system a release job is running in.
+///
+/// Use [`detect_ci_environment`] to get the implementation for the
+/// current environment.
+pub trait CiEnvironment: fmt::Debug {
+    /// Name of the CI system, e.g. "GitHub Actions".
+    fn name(&self) -> &'static str;
+
+    /// Get the SHA of the commit the job is running for.
+    fn commit_sha(&self) -> Result<String, VarError>;
+
+    /// Get the full git ref the job is running for, e.g.
+    /// `refs/heads/main` or `refs/tags/v1.0.0`.
+    fn git_ref(&self) -> Option<String>;
+
+    /// Get the kind of event that triggered the job.
+    fn event_kind(&self) -> Option<CiEventKind>;
+
+    /// Get the repository slug, e.g. `owner/repo`.
+    fn repository(&self) -> Option<String>;
+
+    /// Get a URL for viewing the job's run.
+    fn run_url(&self) -> Option<String>;
+
+    /// Whether the job is running for a pull request from a fork.
+    ///
+    /// Returns false if this can't be determined.
+    fn is_fork_pull_request(&self) -> bool {
+        false
+    }
+
+    /// Get the branch the job is running for, if the ref is a branch.
+    fn branch(&self) -> Option<String> {
+        self.git_ref()?
+            .strip_prefix("refs/heads/")
+            .map(|branch| branch.to_string())
+    }
+}
+
+/// Detect which CI system the current process is running in.
+///
+/// If no known CI system is detected, [`GenericCi`] is returned. As an
+/// exception, if only `GITHUB_SHA` is set (as with [`get_github_sha`]),
+/// [`GithubActions`] is returned, so that scripts which set it by hand
+/// keep working.
+pub fn detect_ci_environment() -> Box<dyn CiEnvironment> {
+    detect_ci_environment_from(Vars::default())
+}
+
+fn detect_ci_environment_from(vars: Vars) -> Box<dyn CiEnvironment> {
+    if vars.is_true("GITHUB_ACTIONS") {
+        Box::new(GithubActions { vars })
+    } else if vars.is_true("GITLAB_CI") {
+        Box::new(GitlabCi { vars })
+    } else if vars.is_true("BUILDKITE") {
+        Box::new(Buildkite { vars })
+    } else if vars.is_true("CIRCLECI") {
+        Box::new(CircleCi { vars })
+    } else if vars.get("JENKINS_URL").is_some() {
+        Box::new(Jenkins { vars })
+    } else if vars.get("GITHUB_SHA").is_some() {
+        Box::new(GithubActions { vars })
+    } else {
+        Box::new(GenericCi { vars })
+    }
+}
+
+/// Source of environment variables.
+///
+/// By default the process environment is used. Tests can supply a
+/// fixed set of variables instead.
+#[derive(Clone, Debug, Default)]
+struct Vars(Option<BTreeMap<String, String>>);
+
+impl Vars {
+    /// Get a variable. Empty values are treated as unset.
+    fn get(&self, name: &str) -> Option<String> {
+        let val = match &self.0 {
+            Some(vars) => vars.get(name).cloned(),
+            None => env::var(name).ok(),
+        };
+        val.filter(|val| !val.is_empty())
+    }
+
+    /// Get a variable, or an error if it is not set.
+    fn require(&self, name: &str) -> Result<String, VarError> {
+        match &self.0 {
+            Some(vars) => {
+                vars.get(name).cloned().ok_or(env::VarError::NotPresent)
+            }
+            None => env::var(name),
+        }
+        .map_err(|err| VarError {
+            name: name.to_owned(),
+            err,
+        })
+    }
+
+    fn is_true(&self, name: &str) -> bool {
+        self.get(name)
+            .is_some_and(|val| val.eq_ignore_ascii_case("true"))
+    }
+}
+
+/// Get a full git ref from optional tag and branch names.
+fn tag_or_branch_ref(
+    tag: Option<String>,
+    branch: Option<String>,
+) -> Option<String> {
+    if let Some(tag) = tag {
+        Some(format!("refs/tags/{tag}"))
+    } else {
+        branch.map(|branch| format!("refs/heads/{branch}"))
+    }
+}
+
+/// Get an `owner/repo` slug from a git remote URL, such as
+/// `git@github.com:owner/repo.git` or `https://github.com/owner/repo`.
+pub(crate) fn repo_slug_from_url(url: &str) -> Option<String> {
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    let mut parts = url.rsplit(['/', ':']);
+    let repo = parts.next().filter(|s| !s.is_empty())?;
+    let owner = parts.next().filter(|s| !s.is_empty())?;
+    Some(format!("{owner}/{repo}"))
+}
+
+/// [GitHub Actions].
+///
+/// [GitHub Actions]: https://docs.github.com/en/actions/learn-github-actions/variables
+#[derive(Clone, Debug, Default)]
+pub struct GithubActions {
+    vars: Vars,
+}
+
+impl GithubActions {
+    /// Create a `GithubActions` that reads the process environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CiEnvironment for GithubActions {
+    fn name(&self) -> &'static str {
+        "GitHub Actions"
+    }
+
+    fn commit_sha(&self) -> Result<String, VarError> {
+        self.vars.require("GITHUB_SHA")
+    }
+
+    fn git_ref(&self) -> Option<String> {
+        self.vars.get("GITHUB_REF")
+    }
+
+    fn event_kind(&self) -> Option<CiEventKind> {
+        let event = self.vars.get("GITHUB_EVENT_NAME")?;
+        Some(match event.as_str() {
+            "push" => CiEventKind::Push,
+            "pull_request" | "pull_request_target" => CiEventKind::PullRequest,
+            "merge_group" => CiEventKind::MergeQueue,
+            "schedule" => CiEventKind::Schedule,
+            "workflow_dispatch" => CiEventKind::Manual,
+            "release" => CiEventKind::Release,
+            _ => CiEventKind::Other(event),
+        })
+    }
+
+    fn repository(&self) -> Option<String> {
+        self.vars.get("GITHUB_REPOSITORY")
+    }
+
+    fn run_url(&self) -> Option<String> {
+        Some(format!(
+            "{}/{}/actions/runs/{}",
+            self.vars.get("GITHUB_SERVER_URL")?,
+            self.vars.get("GITHUB_REPOSITORY")?,
+            self.vars.get("GITHUB_RUN_ID")?
+        ))
+    }
+
+    fn is_fork_pull_request(&self) -> bool {
+        // The fork is only known from the event payload.
+        match read_github_event_from(&self.vars) {
+            Ok(GithubEvent::PullRequest(event)) => event.is_from_fork(),
+            _ => false,
+        }
+    }
+}
+
+/// [GitLab CI/CD].
+///
+/// [GitLab CI/CD]: https://docs.gitlab.com/ee/ci/variables/predefined_variables.html
+#[derive(Clone, Debug, Default)]
+pub struct GitlabCi {
+    vars: Vars,
+}
+
+impl GitlabCi {
+    /// Create a `GitlabCi` that reads the process environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CiEnvironment for GitlabCi {
+    fn name(&self) -> &'static str {
+        "GitLab CI"
+    }
+
+    fn commit_sha(&self) -> Result<String, VarError> {
+        self.vars.require("CI_COMMIT_SHA")
+    }
+
+    fn git_ref(&self) -> Option<String> {
+        tag_or_branch_ref(
+            self.vars.get("CI_COMMIT_TAG"),
+            self.vars.get("CI_COMMIT_BRANCH"),
+        )
+        .or_else(|| self.vars.get("CI_MERGE_REQUEST_REF_PATH"))
+    }
+
+    fn event_kind(&self) -> Option<CiEventKind> {
+        if self.vars.get("CI_MERGE_REQUEST_EVENT_TYPE").as_deref()
+            == Some("merge_train")
+        {
+            return Some(CiEventKind::MergeQueue);
+        }
+        let source = self.vars.get("CI_PIPELINE_SOURCE")?;
+        Some(match source.as_str() {
+            "push" => CiEventKind::Push,
+            "merge_request_event" | "external_pull_request_event" => {
+                CiEventKind::PullRequest
+            }
+            "schedule" => CiEventKind::Schedule,
+            "web" => CiEventKind::Manual,
+            _ => CiEventKind::Other(source),
+        })
+    }
+
+    fn repository(&self) -> Option<String> {
+        self.vars.get("CI_PROJECT_PATH")
+    }
+
+    fn run_url(&self) -> Option<String> {
+        self.vars.get("CI_PIPELINE_URL")
+    }
+
+    fn is_fork_pull_request(&self) -> bool {
+        match (
+            self.vars.get("CI_MERGE_REQUEST_SOURCE_PROJECT_ID"),
+            self.vars.get("CI_MERGE_REQUEST_PROJECT_ID"),
+        ) {
+            (Some(source), Some(target)) => source != target,
+            _ => false,
+        }
+    }
+}
+
+/// [Buildkite].
+///
+/// [Buildkite]: https://buildkite.com/docs/pipelines/environment-variables
+#[derive(Clone, Debug, Default)]
+pub struct Buildkite {
+    vars: Vars,
+}
+
+impl Buildkite {
+    /// Create a `Buildkite` that reads the process environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CiEnvironment for Buildkite {
+    fn name(&self) -> &'static str {
+        "Buildkite"
+    }
+
+    fn commit_sha(&self) -> Result<String, VarError> {
+        self.vars.require("BUILDKITE_COMMIT")
+    }
+
+    fn git_ref(&self) -> Option<String> {
+        tag_or_branch_ref(
+            self.vars.get("BUILDKITE_TAG"),
+            self.vars.get("BUILDKITE_BRANCH"),
+        )
+    }
+
+    fn event_kind(&self) -> Option<CiEventKind> {
+        if self
+            .vars
+            .get("BUILDKITE_PULL_REQUEST")
+            .is_some_and(|pr| pr != "false")
+        {
+            return Some(CiEventKind::PullRequest);
+        }
+        let source = self.vars.get("BUILDKITE_SOURCE")?;
+        Some(match source.as_str() {
+            "webhook" => CiEventKind::Push,
+            "schedule" => CiEventKind::Schedule,
+            "ui" => CiEventKind::Manual,
+            _ => CiEventKind::Other(source),
+        })
+    }
+
+    fn repository(&self) -> Option<String> {
+        repo_slug_from_url(&self.vars.get("BUILDKITE_REPO")?)
+    }
+
+    fn run_url(&self) -> Option<String> {
+        self.vars.get("BUILDKITE_BUILD_URL")
+    }
+
+    fn is_fork_pull_request(&self) -> bool {
+        match (
+            self.vars.get("BUILDKITE_PULL_REQUEST_REPO"),
+            self.vars.get("BUILDKITE_REPO"),
+        ) {
+            (Some(source), Some(target)) => {
+                repo_slug_from_url(&source) != repo_slug_from_url(&target)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// [CircleCI].
+///
+/// [CircleCI]: https://circleci.com/docs/variables/#built-in-environment-variables
+#[derive(Clone, Debug, Default)]
+pub struct CircleCi {
+    vars: Vars,
+}
+
+impl CircleCi {
+    /// Create a `CircleCi` that reads the process environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CiEnvironment for CircleCi {
+    fn name(&self) -> &'static str {
+        "CircleCI"
+    }
+
+    fn commit_sha(&self) -> Result<String, VarError> {
+        self.vars.require("CIRCLE_SHA1")
+    }
+
+    fn git_ref(&self) -> Option<String> {
+        tag_or_branch_ref(
+            self.vars.get("CIRCLE_TAG"),
+            self.vars.get("CIRCLE_BRANCH"),
+        )
+    }
+
+    fn event_kind(&self) -> Option<CiEventKind> {
+        if self.vars.get("CIRCLE_PULL_REQUEST").is_some() {
+            Some(CiEventKind::PullRequest)
+        } else {
+            Some(CiEventKind::Push)
+        }
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!(
+            "{}/{}",
+            self.vars.get("CIRCLE_PROJECT_USERNAME")?,
+            self.vars.get("CIRCLE_PROJECT_REPONAME")?
+        ))
+    }
+
+    fn run_url(&self) -> Option<String> {
+        self.vars.get("CIRCLE_BUILD_URL")
+    }
+
+    fn is_fork_pull_request(&self) -> bool {
+        // Only set for pull requests from forks.
+        self.vars.get("CIRCLE_PR_NUMBER").is_some()
+    }
+}
+
+/// [Jenkins], using the variables set by the Git plugin and by
+/// multibranch pipelines.
+///
+/// [Jenkins]: https://www.jenkins.io/doc/book/pipeline/jenkinsfile/#using-environment-variables
+#[derive(Clone, Debug, Default)]
+pub struct Jenkins {
+    vars: Vars,
+}
+
+impl Jenkins {
+    /// Create a `Jenkins` that reads the process environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CiEnvironment for Jenkins {
+    fn name(&self) -> &'static str {
+        "Jenkins"
+    }
+
+    fn commit_sha(&self) -> Result<String, VarError> {
+        self.vars.require("GIT_COMMIT")
+    }
+
+    fn git_ref(&self) -> Option<String> {
+        // The Git plugin's `GIT_BRANCH` includes the remote name,
+        // e.g. "origin/main".
+        let branch = self.vars.get("BRANCH_NAME").or_else(|| {
+            let branch = self.vars.get("GIT_BRANCH")?;
+            Some(match branch.split_once('/') {
+                Some((_remote, branch)) => branch.to_string(),
+                None => branch,
+            })
+        });
+        tag_or_branch_ref(self.vars.get("TAG_NAME"), branch)
+    }
+
+    fn event_kind(&self) -> Option<CiEventKind> {
+        if self.vars.get("CHANGE_ID").is_some() {
+            Some(CiEventKind::PullRequest)
+        } else {
+            None
+        }
+    }
+
+    fn repository(&self) -> Option<String> {
+        repo_slug_from_url(&self.vars.get("GIT_URL")?)
+    }
+
+    fn run_url(&self) -> Option<String> {
+        self.vars.get("BUILD_URL")
+    }
+
+    fn is_fork_pull_request(&self) -> bool {
+        // Only set for pull requests from forks.
+        self.vars.get("CHANGE_FORK").is_some()
+    }
+}
+
+/// Fallback for unknown CI systems.
+///
+/// The commit is read from `GIT_COMMIT`, and the branch from
+/// `GIT_BRANCH`. Other information is not available.
+#[derive(Clone, Debug, Default)]
+pub struct GenericCi {
+    vars: Vars,
+}
+
+impl GenericCi {
+    /// Create a `GenericCi` that reads the process environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CiEnvironment for GenericCi {
+    fn name(&self) -> &'static str {
+        "generic CI"
+    }
+
+    fn commit_sha(&self) -> Result<String, VarError> {
+        self.vars.require("GIT_COMMIT")
+    }
+
+    fn git_ref(&self) -> Option<String> {
+        tag_or_branch_ref(None, self.vars.get("GIT_BRANCH"))
+    }
+
+    fn event_kind(&self) -> Option<CiEventKind> {
+        None
+    }
+
+    fn repository(&self) -> Option<String> {
+        None
+    }
+
+    fn run_url(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Error returned by [`read_github_event`] and
+/// [`GithubEvent::from_file`].
+#[derive(Debug)]
+pub enum ReadGithubEventError {
+    /// Environment error.
+    Env(VarError),
+
+    /// Failed to read the event file with `jq`.
+    Process(RunCommandError),
+
+    /// The event file does not have the expected structure.
+    Malformed {
+        /// Path of the event file.
+        path: PathBuf,
+    },
+}
+
+impl Display for ReadGithubEventError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to read github event: ")?;
+        match self {
+            Self::Env(err) => write!(f, "{err}"),
+            Self::Process(_) => write!(f, "failed to parse event file"),
+            Self::Malformed { path } => {
+                write!(f, "unexpected contents in {}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReadGithubEventError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Env(err) => Some(err),
+            Self::Process(err) => Some(err),
+            Self::Malformed { .. } => None,
+        }
+    }
+}
+
+/// Event that triggered a GitHub Actions workflow.
+///
+/// Only the fields needed for releasing are included. See the
+/// [webhook events] documentation for details of the payloads.
+///
+/// [webhook events]: https://docs.github.com/en/webhooks/webhook-events-and-payloads
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GithubEvent {
+    /// `push` event.
+    Push(PushEvent),
+
+    /// `workflow_dispatch` event.
+    WorkflowDispatch(WorkflowDispatchEvent),
+
+    /// `release` event.
+    Release(ReleaseEvent),
+
+    /// `pull_request` or `pull_request_target` event.
+    PullRequest(PullRequestEvent),
+
+    /// Any other event. Contains the event name.
+    Other(String),
+}
+
+/// Payload of a `push` event.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PushEvent {
+    /// SHA of the most recent commit on the ref before the push.
+    pub before: String,
+
+    /// SHA of the most recent commit on the ref after the push.
+    pub after: String,
+
+    /// Full ref that was pushed, e.g. `refs/heads/main`.
+    pub git_ref: String,
+
+    /// Whether the push was a force push.
+    pub forced: bool,
+
+    /// Pushed commits, oldest first. GitHub includes at most 2048
+    /// commits.
+    pub commits: Vec<EventCommit>,
+
+    /// The most recent commit on the ref after the push, if any.
+    pub head_commit: Option<EventCommit>,
+}
+
+impl PushEvent {
+    /// Find a commit in the event by SHA.
+    pub fn find_commit(&self, commit_sha: &str) -> Option<&EventCommit> {
+        self.head_commit
+            .iter()
+            .chain(&self.commits)
+            .find(|commit| commit.id == commit_sha)
+    }
+}
+
+/// A commit in a [`PushEvent`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EventCommit {
+    /// Commit SHA.
+    pub id: String,
+
+    /// Full commit message.
+    pub message: String,
+}
+
+impl EventCommit {
+    /// Get the subject of the commit message.
+    ///
+    /// As with git's `%s` format, this is the first paragraph of the
+    /// message with line breaks replaced by spaces.
+    pub fn subject(&self) -> String {
+        self.message
+            .lines()
+            .take_while(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Get the body of the commit message.
+    ///
+    /// As with git's `%b` format, this is everything after the first
+    /// paragraph, ending in a newline if not empty.
+    pub fn body(&self) -> String {
+        let body: Vec<_> = self
+            .message
+            .lines()
+            .skip_while(|line| !line.trim().is_empty())
+            .skip_while(|line| line.trim().is_empty())
+            .collect();
+        let body = body.join("\n");
+        let body = body.trim_end();
+        if body.is_empty() {
+            String::new()
+        } else {
+            format!("{body}\n")
+        }
+    }
+}
+
+/// Payload of a `workflow_dispatch` event.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WorkflowDispatchEvent {
+    /// Full ref the workflow was run on, e.g. `refs/heads/main`.
+    pub git_ref: String,
+
+    /// Workflow inputs. Non-string inputs are converted to strings.
+    pub inputs: BTreeMap<String, String>,
+}
+
+/// Payload of a `release` event.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReleaseEvent {
+    /// Action that was performed, e.g. `published`.
+    pub action: String,
+
+    /// Name of the release's tag.
+    pub tag_name: String,
+
+    /// Title of the release.
+    pub name: Option<String>,
+
+    /// Whether the release is a draft.
+    pub draft: bool,
+
+    /// Whether the release is a prerelease.
+    pub prerelease: bool,
+}
+
+/// Payload of a `pull_request` or `pull_request_target` event.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PullRequestEvent {
+    /// Action that was performed, e.g. `opened` or `synchronize`.
+    pub action: String,
+
+    /// Pull request number.
+    pub number: u64,
+
+    /// Name of the branch the changes come from.
+    pub head_ref: String,
+
+    /// SHA of the most recent commit on the head branch.
+    pub head_sha: String,
+
+    /// Slug (`owner/repo`) of the repository the changes come from.
+    /// This is `None` if that repository has been deleted.
+    pub head_repo: Option<String>,
+
+    /// Name of the branch the changes will be merged into.
+    pub base_ref: String,
+
+    /// Slug (`owner/repo`) of the repository the changes will be merged
+    /// into.
+    pub base_repo: String,
+
+    /// Whether the pull request has been merged.
+    pub merged: bool,
+}
+
+impl PullRequestEvent {
+    /// Whether the pull request comes from a different repository
+    /// than the one it will be merged into.
+    pub fn is_from_fork(&self) -> bool {
+        self.head_repo.as_deref() != Some(self.base_repo.as_str())
+    }
+}
+
+/// Read the event that triggered the current GitHub Actions workflow.
+///
+/// The event name is read from `GITHUB_EVENT_NAME`, and the payload
+/// from the file at `GITHUB_EVENT_PATH`.
+///
+/// This is useful when the checkout does not have the full history,
+/// since the `push` payload includes the pushed commit messages.
+pub fn read_github_event() -> Result<GithubEvent, ReadGithubEventError> {
+    read_github_event_from(&Vars::default())
+}
+
+fn read_github_event_from(
+    vars: &Vars,
+) -> Result<GithubEvent, ReadGithubEventError> {
+    let name = vars
+        .require("GITHUB_EVENT_NAME")
+        .map_err(ReadGithubEventError::Env)?;
+    let path = vars
+        .require("GITHUB_EVENT_PATH")
+        .map_err(ReadGithubEventError::Env)?;
+    GithubEvent::from_file(&name, Path::new(&path))
+}
+
+impl GithubEvent {
+    /// Read an event payload from a JSON file.
+    ///
+    /// `name` is the event name, e.g. `push`, which determines how the
+    /// payload is parsed.
+    pub fn from_file(
+        name: &str,
+        path: &Path,
+    ) -> Result<Self, ReadGithubEventError> {
+        let filter = match name {
+            "push" => {
+                r#".before, .after, .ref, .forced,
+                   (.commits // [] | length),
+                   (.commits // [] | .[] | .id, .message),
+                   (.head_commit != null),
+                   .head_commit.id, .head_commit.message"#
+            }
+            "workflow_dispatch" => {
+                r#".ref, (.inputs // {} | length),
+                   (.inputs // {} | to_entries[] | .key, .value)"#
+            }
+            "release" => {
+                r#".action, .release.tag_name, .release.name,
+                   .release.draft, .release.prerelease"#
+            }
+            "pull_request" | "pull_request_target" => {
+                r#".action, .number,
+                   .pull_request.head.ref, .pull_request.head.sha,
+                   .pull_request.head.repo.full_name,
+                   .pull_request.base.ref,
+                   .pull_request.base.repo.full_name,
+                   .pull_request.merged"#
+            }
+            _ => return Ok(Self::Other(name.to_string())),
+        };
+
+        let output = query_json_file(path, filter)
+            .map_err(ReadGithubEventError::Process)?;
+        let mut fields = EventFields {
+            fields: output.split('\0'),
+            path,
+        };
+
+        Ok(match name {
+            "push" => {
+                let mut event = PushEvent {
+                    before: fields.string()?,
+                    after: fields.string()?,
+                    git_ref: fields.string()?,
+                    forced: fields.bool()?,
+                    ..Default::default()
+                };
+                for _ in 0..fields.number()? {
+                    event.commits.push(EventCommit {
+                        id: fields.string()?,
+                        message: fields.string()?,
+                    });
+                }
+                let has_head_commit = fields.bool()?;
+                let head_commit = EventCommit {
+                    id: fields.string()?,
+                    message: fields.string()?,
+                };
+                if has_head_commit {
+                    event.head_commit = Some(head_commit);
+                }
+                Self::Push(event)
+            }
+            "workflow_dispatch" => {
+                let mut event = WorkflowDispatchEvent {
+                    git_ref: fields.string()?,
+                    ..Default::default()
+                };
+                for _ in 0..fields.number()? {
+                    event.inputs.insert(fields.string()?, fields.string()?);
+                }
+                Self::WorkflowDispatch(event)
+            }
+            "release" => Self::Release(ReleaseEvent {
+                action: fields.string()?,
+                tag_name: fields.string()?,
+                name: fields.optional_string()?,
+                draft: fields.bool()?,
+                prerelease: fields.bool()?,
+            }),
+            _ => Self::PullRequest(PullRequestEvent {
+                action: fields.string()?,
+                number: fields.number()?,
+                head_ref: fields.string()?,
+                head_sha: fields.string()?,
+                head_repo: fields.optional_string()?,
+                base_ref: fields.string()?,
+                base_repo: fields.string()?,
+                merged: fields.bool()?,
+            }),
+        })
+    }
+}
+
+/// Use `jq` to get values from a JSON file.
+///
+/// Each value produced by `filter` is converted to a string and
+/// terminated with a NUL byte, so that values containing newlines can be
+/// separated reliably. Null values become empty strings.
+fn query_json_file(
+    path: &Path,
+    filter: &str,
+) -> Result<String, RunCommandError> {
+    let mut cmd = Command::new("jq");
+    // Don't add newlines between outputs.
+    cmd.arg("--join-output");
+    cmd.arg(format!(
+        r#"{filter} | (if . == null then "" else tostring end), "\u0000""#,
+    ));
+    cmd.arg(path);
+    get_cmd_stdout_utf8(cmd)
+}
+
+/// Iterator over the fields output by [`query_json_file`].
+struct EventFields<'a> {
+    fields: std::str::Split<'a, char>,
+    path: &'a Path,
+}
+
+impl EventFields<'_> {
+    fn malformed(&self) -> ReadGithubEventError {
+        ReadGithubEventError::Malformed {
+            path: self.path.to_owned(),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ReadGithubEventError> {
+        self.fields
+            .next()
+            .map(|field| field.to_string())
+            .ok_or_else(|| self.malformed())
+    }
+
+    fn optional_string(
+        &mut self,
+    ) -> Result<Option<String>, ReadGithubEventError> {
+        let field = self.string()?;
+        Ok(if field.is_empty() { None } else { Some(field) })
+    }
+
+    fn bool(&mut self) -> Result<bool, ReadGithubEventError> {
+        match self.string()?.as_str() {
+            "true" => Ok(true),
+            "false" | "" => Ok(false),
+            _ => Err(self.malformed()),
+        }
+    }
+
+    fn number<T: FromStr>(&mut self) -> Result<T, ReadGithubEventError> {
+        self.string()?.parse().map_err(|_| self.malformed())
+    }
+}
+
+/// Error returned by [`set_github_output`] and
+/// [`append_github_step_summary`].
+#[derive(Debug)]
+pub enum WriteGithubFileError {
+    /// Environment error.
+    Env(VarError),
+
+    /// Failed to write to the file.
+    Io {
+        /// Path of the file.
+        path: PathBuf,
+        /// Underlying error.
+        err: io::Error,
+    },
+}
+
+impl Display for WriteGithubFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Env(err) => write!(f, "{err}"),
+            Self::Io { path, .. } => {
+                write!(f, "failed to write to {}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for WriteGithubFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Env(err) => Some(err),
+            Self::Io { err, .. } => Some(err),
+        }
+    }
+}
+
+/// Set a GitHub Actions step output.
+///
+/// The output is appended to the file at `GITHUB_OUTPUT`. Later steps
+/// in the job can read it as `steps.<step id>.outputs.<name>`.
+///
+/// Values containing line breaks are written with a randomly-generated
+/// delimiter, as described in the [multiline strings] documentation.
+///
+/// [multiline strings]: https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#multiline-strings
+pub fn set_github_output(
+    name: &str,
+    value: &str,
+) -> Result<(), WriteGithubFileError> {
+    append_to_github_file("GITHUB_OUTPUT", &format_github_output(name, value))
+}
+
+/// Append markdown to the GitHub Actions job summary.
+///
+/// The markdown is appended to the file at `GITHUB_STEP_SUMMARY`, and
+/// is shown on the workflow run's summary page.
+pub fn append_github_step_summary(
+    markdown: &str,
+) -> Result<(), WriteGithubFileError> {
+    let mut markdown = markdown.to_string();
+    if !markdown.ends_with('\n') {
+        markdown.push('\n');
+    }
+    append_to_github_file("GITHUB_STEP_SUMMARY", &markdown)
+}
+
+/// Append `contents` to the file whose path is in the env var
+/// `var_name`.
+fn append_to_github_file(
+    var_name: &str,
+    contents: &str,
+) -> Result<(), WriteGithubFileError> {
+    let path = Vars::default()
+        .require(var_name)
+        .map_err(WriteGithubFileError::Env)?;
+    let path = PathBuf::from(path);
+    let io_err = |err| WriteGithubFileError::Io {
+        path: path.clone(),
+        err,
+    };
+
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&path)
+        .map_err(io_err)?;
+    file.write_all(contents.as_bytes()).map_err(io_err)
+}
+
+/// Format a step output in the syntax of the `GITHUB_OUTPUT` file.
+fn format_github_output(name: &str, value: &str) -> String {
+    if !value.contains(['\n', '\r']) {
+        return format!("{name}={value}\n");
+    }
+
+    // Generate a delimiter that does not appear in the value. The
+    // hasher is randomly seeded, so this doesn't need a dependency on
+    // a random number crate.
+    let delimiter = loop {
+        let random = RandomState::new().build_hasher().finish();
+        let delimiter = format!("ghadelimiter_{random:016x}");
+        if !value.contains(&delimiter) {
+            break delimiter;
+        }
+    };
+    format!("{name}<<{delimiter}\n{value}\n{delimiter}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn vars(vars: &[(&str, &str)]) -> Vars {
+        Vars(Some(
+            vars.iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        ))
+    }
+
+    #[test]
+    fn test_detect_github_actions() {
+        let ci = detect_ci_environment_from(vars(&[
+            ("GITHUB_ACTIONS", "true"),
+            ("GITHUB_SHA", "abc"),
+            ("GITHUB_REF", "refs/heads/main"),
+            ("GITHUB_EVENT_NAME", "push"),
+            ("GITHUB_REPOSITORY", "owner/repo"),
+            ("GITHUB_SERVER_URL", "https://github.com"),
+            ("GITHUB_RUN_ID", "123"),
+        ]));
+        assert_eq!(ci.name(), "GitHub Actions");
+        assert_eq!(ci.commit_sha().unwrap(), "abc");
+        assert_eq!(ci.git_ref().unwrap(), "refs/heads/main");
+        assert_eq!(ci.branch().unwrap(), "main");
+        assert_eq!(ci.event_kind(), Some(CiEventKind::Push));
+        assert_eq!(ci.repository().unwrap(), "owner/repo");
+        assert_eq!(
+            ci.run_url().unwrap(),
+            "https://github.com/owner/repo/actions/runs/123"
+        );
+
+        let ci = detect_ci_environment_from(vars(&[
+            ("GITHUB_ACTIONS", "true"),
+            ("GITHUB_REF", "refs/pull/1/merge"),
+            ("GITHUB_EVENT_NAME", "pull_request_target"),
+        ]));
+        assert_eq!(
+            ci.commit_sha().unwrap_err(),
+            VarError {
+                name: "GITHUB_SHA".to_string(),
+                err: env::VarError::NotPresent,
+            }
+        );
+        assert_eq!(ci.branch(), None);
+        assert_eq!(ci.event_kind(), Some(CiEventKind::PullRequest));
+        assert_eq!(ci.run_url(), None);
+        // No event payload.
+        assert!(!ci.is_fork_pull_request());
+    }
+
+    #[test]
+    fn test_detect_gitlab_ci() {
+        let ci = detect_ci_environment_from(vars(&[
+            ("GITLAB_CI", "true"),
+            ("CI_COMMIT_SHA", "abc"),
+            ("CI_COMMIT_BRANCH", "main"),
+            ("CI_PIPELINE_SOURCE", "push"),
+            ("CI_PROJECT_PATH", "group/project"),
+            (
+                "CI_PIPELINE_URL",
+                "https://gitlab.com/group/project/-/pipelines/1",
+            ),
+        ]));
+        assert_eq!(ci.name(), "GitLab CI");
+        assert_eq!(ci.commit_sha().unwrap(), "abc");
+        assert_eq!(ci.git_ref().unwrap(), "refs/heads/main");
+        assert_eq!(ci.event_kind(), Some(CiEventKind::Push));
+        assert_eq!(ci.repository().unwrap(), "group/project");
+        assert_eq!(
+            ci.run_url().unwrap(),
+            "https://gitlab.com/group/project/-/pipelines/1"
+        );
+
+        let ci = detect_ci_environment_from(vars(&[
+            ("GITLAB_CI", "true"),
+            ("CI_COMMIT_TAG", "v1.0.0"),
+            ("CI_PIPELINE_SOURCE", "merge_request_event"),
+            ("CI_MERGE_REQUEST_EVENT_TYPE", "merge_train"),
+        ]));
+        assert_eq!(ci.git_ref().unwrap(), "refs/tags/v1.0.0");
+        assert_eq!(ci.event_kind(), Some(CiEventKind::MergeQueue));
+        assert!(!ci.is_fork_pull_request());
+
+        let ci = detect_ci_environment_from(vars(&[
+            ("GITLAB_CI", "true"),
+            ("CI_PIPELINE_SOURCE", "merge_request_event"),
+            ("CI_MERGE_REQUEST_SOURCE_PROJECT_ID", "2"),
+            ("CI_MERGE_REQUEST_PROJECT_ID", "1"),
+        ]));
+        assert!(ci.is_fork_pull_request());
+    }
+
+    #[test]
+    fn test_detect_buildkite() {
+        let ci = detect_ci_environment_from(vars(&[
+            ("BUILDKITE", "true"),
+            ("BUILDKITE_COMMIT", "abc"),
+            ("BUILDKITE_BRANCH", "main"),
+            ("BUILDKITE_PULL_REQUEST", "false"),
+            ("BUILDKITE_SOURCE", "webhook"),
+            ("BUILDKITE_REPO", "git@github.com:owner/repo.git"),
+        ]));
+        assert_eq!(ci.name(), "Buildkite");
+        assert_eq!(ci.commit_sha().unwrap(), "abc");
+        assert_eq!(ci.branch().unwrap(), "main");
+        assert_eq!(ci.event_kind(), Some(CiEventKind::Push));
+        assert_eq!(ci.repository().unwrap(), "owner/repo");
+
+        let ci = detect_ci_environment_from(vars(&[
+            ("BUILDKITE", "true"),
+            ("BUILDKITE_PULL_REQUEST", "12"),
+            (
+                "BUILDKITE_PULL_REQUEST_REPO",
+                "https://github.com/owner/repo",
+            ),
+            ("BUILDKITE_REPO", "git@github.com:owner/repo.git"),
+        ]));
+        assert_eq!(ci.event_kind(), Some(CiEventKind::PullRequest));
+        assert!(!ci.is_fork_pull_request());
+    }
+
+    #[test]
+    fn test_detect_circleci() {
+        let ci = detect_ci_environment_from(vars(&[
+            ("CIRCLECI", "true"),
+            ("CIRCLE_SHA1", "abc"),
+            ("CIRCLE_TAG", "v1.0.0"),
+            ("CIRCLE_PROJECT_USERNAME", "owner"),
+            ("CIRCLE_PROJECT_REPONAME", "repo"),
+        ]));
+        assert_eq!(ci.name(), "CircleCI");
+        assert_eq!(ci.commit_sha().unwrap(), "abc");
+        assert_eq!(ci.git_ref().unwrap(), "refs/tags/v1.0.0");
+        assert_eq!(ci.event_kind(), Some(CiEventKind::Push));
+        assert_eq!(ci.repository().unwrap(), "owner/repo");
+        assert!(!ci.is_fork_pull_request());
+
+        let ci = detect_ci_environment_from(vars(&[
+            ("CIRCLECI", "true"),
+            (
+                "CIRCLE_PULL_REQUEST",
+                "https://github.com/owner/repo/pull/1",
+            ),
+            ("CIRCLE_PR_NUMBER", "1"),
+        ]));
+        assert_eq!(ci.event_kind(), Some(CiEventKind::PullRequest));
+        assert!(ci.is_fork_pull_request());
+    }
+
+    #[test]
+    fn test_detect_jenkins() {
+        let ci = detect_ci_environment_from(vars(&[
+            ("JENKINS_URL", "https://jenkins.example.com"),
+            ("GIT_COMMIT", "abc"),
+            ("GIT_BRANCH", "origin/main"),
+            ("GIT_URL", "https://github.com/owner/repo.git"),
+        ]));
+        assert_eq!(ci.name(), "Jenkins");
+        assert_eq!(ci.commit_sha().unwrap(), "abc");
+        assert_eq!(ci.branch().unwrap(), "main");
+        assert_eq!(ci.event_kind(), None);
+        assert_eq!(ci.repository().unwrap(), "owner/repo");
+    }
+
+    #[test]
+    fn test_detect_generic() {
+        let ci = detect_ci_environment_from(vars(&[
+            ("GIT_COMMIT", "abc"),
+            ("GIT_BRANCH", "main"),
+        ]));
+        assert_eq!(ci.name(), "generic CI");
+        assert_eq!(ci.commit_sha().unwrap(), "abc");
+        assert_eq!(ci.branch().unwrap(), "main");
+        assert_eq!(ci.repository(), None);
+
+        let ci = detect_ci_environment_from(vars(&[]));
+        assert!(ci.commit_sha().is_err());
+        assert_eq!(ci.git_ref(), None);
+
+        // `GITHUB_SHA` on its own is still enough.
+        let ci = detect_ci_environment_from(vars(&[("GITHUB_SHA", "abc")]));
+        assert_eq!(ci.name(), "GitHub Actions");
+        assert_eq!(ci.commit_sha().unwrap(), "abc");
+    }
+
+    #[test]
+    fn test_repo_slug_from_url() {
+        assert_eq!(
+            repo_slug_from_url("git@github.com:owner/repo.git").unwrap(),
+            "owner/repo"
+        );
+        assert_eq!(
+            repo_slug_from_url("https://github.com/owner/repo/").unwrap(),
+            "owner/repo"
+        );
+        assert_eq!(repo_slug_from_url("repo"), None);
+    }
+
+    fn write_event(dir: &TempDir, json: &str) -> PathBuf {
+        let path = dir.path().join("event.json");
+        fs::write(&path, json).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_push_event() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = write_event(
+            &tmp_dir,
+            r#"{
+  "before": "aaa",
+  "after": "ccc",
+  "ref": "refs/heads/main",
+  "forced": false,
+  "commits": [
+    {"id": "bbb", "message": "first\n\nbody one"},
+    {"id": "ccc", "message": "release: second\nline two\n\nbody two\n\nmore"}
+  ],
+  "head_commit": {"id": "ccc", "message": "release: second\nline two\n\nbody two\n\nmore"}
+}"#,
+        );
+        let GithubEvent::Push(event) =
+            GithubEvent::from_file("push", &path).unwrap()
+        else {
+            panic!("wrong event type");
+        };
+        assert_eq!(event.before, "aaa");
+        assert_eq!(event.after, "ccc");
+        assert_eq!(event.git_ref, "refs/heads/main");
+        assert!(!event.forced);
+        assert_eq!(event.commits.len(), 2);
+        assert_eq!(event.commits[0].id, "bbb");
+        assert_eq!(event.commits[0].message, "first\n\nbody one");
+
+        let commit = event.find_commit("ccc").unwrap();
+        assert_eq!(commit.subject(), "release: second line two");
+        assert_eq!(commit.body(), "body two\n\nmore\n");
+        assert_eq!(event.find_commit("bbb").unwrap().body(), "body one\n");
+        assert!(event.find_commit("ddd").is_none());
+
+        // Branch deletion has no head commit.
+        let path = write_event(
+            &tmp_dir,
+            r#"{"before": "aaa", "after": "000", "ref": "refs/heads/x",
+                "forced": true, "commits": [], "head_commit": null}"#,
+        );
+        let GithubEvent::Push(event) =
+            GithubEvent::from_file("push", &path).unwrap()
+        else {
+            panic!("wrong event type");
+        };
+        assert!(event.forced);
+        assert!(event.commits.is_empty());
+        assert_eq!(event.head_commit, None);
+
+        // Missing commits are treated as empty.
+        let path = write_event(
+            &tmp_dir,
+            r#"{"before": "aaa", "after": "bbb", "ref": "refs/heads/x",
+                "forced": false, "commits": null, "head_commit": null}"#,
+        );
+        let GithubEvent::Push(event) =
+            GithubEvent::from_file("push", &path).unwrap()
+        else {
+            panic!("wrong event type");
+        };
+        assert!(event.commits.is_empty());
+    }
+
+    #[test]
+    fn test_workflow_dispatch_event() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = write_event(
+            &tmp_dir,
+            r#"{"ref": "refs/heads/main",
+                "inputs": {"package": "foo", "dry-run": true}}"#,
+        );
+        assert_eq!(
+            GithubEvent::from_file("workflow_dispatch", &path).unwrap(),
+            GithubEvent::WorkflowDispatch(WorkflowDispatchEvent {
+                git_ref: "refs/heads/main".to_string(),
+                inputs: BTreeMap::from([
+                    ("dry-run".to_string(), "true".to_string()),
+                    ("package".to_string(), "foo".to_string()),
+                ]),
+            })
+        );
+
+        // Workflows without inputs have null inputs.
+        let path = write_event(
+            &tmp_dir,
+            r#"{"ref": "refs/heads/main", "inputs": null}"#,
+        );
+        assert_eq!(
+            GithubEvent::from_file("workflow_dispatch", &path).unwrap(),
+            GithubEvent::WorkflowDispatch(WorkflowDispatchEvent {
+                git_ref: "refs/heads/main".to_string(),
+                inputs: BTreeMap::new(),
+            })
+        );
+
+        let path = write_event(&tmp_dir, r#"{"ref": "refs/heads/main"}"#);
+        assert_eq!(
+            GithubEvent::from_file("workflow_dispatch", &path).unwrap(),
+            GithubEvent::WorkflowDispatch(WorkflowDispatchEvent {
+                git_ref: "refs/heads/main".to_string(),
+                inputs: BTreeMap::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_release_event() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = write_event(
+            &tmp_dir,
+            r#"{"action": "published",
+                "release": {"tag_name": "foo-v1.0.0", "name": null,
+                            "draft": false, "prerelease": true}}"#,
+        );
+        assert_eq!(
+            GithubEvent::from_file("release", &path).unwrap(),
+            GithubEvent::Release(ReleaseEvent {
+                action: "published".to_string(),
+                tag_name: "foo-v1.0.0".to_string(),
+                name: None,
+                draft: false,
+                prerelease: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_pull_request_event() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = write_event(
+            &tmp_dir,
+            r#"{"action": "opened", "number": 12,
+                "pull_request": {
+                  "head": {"ref": "feature", "sha": "abc",
+                           "repo": {"full_name": "someone/repo"}},
+                  "base": {"ref": "main",
+                           "repo": {"full_name": "owner/repo"}},
+                  "merged": false}}"#,
+        );
+        let GithubEvent::PullRequest(event) =
+            GithubEvent::from_file("pull_request_target", &path).unwrap()
+        else {
+            panic!("wrong event type");
+        };
+        assert_eq!(
+            event,
+            PullRequestEvent {
+                action: "opened".to_string(),
+                number: 12,
+                head_ref: "feature".to_string(),
+                head_sha: "abc".to_string(),
+                head_repo: Some("someone/repo".to_string()),
+                base_ref: "main".to_string(),
+                base_repo: "owner/repo".to_string(),
+                merged: false,
+            }
+        );
+        assert!(event.is_from_fork());
+
+        let ci = detect_ci_environment_from(vars(&[
+            ("GITHUB_ACTIONS", "true"),
+            ("GITHUB_EVENT_NAME", "pull_request_target"),
+            ("GITHUB_EVENT_PATH", path.to_str().unwrap()),
+        ]));
+        assert!(ci.is_fork_pull_request());
+    }
+
+    #[test]
+    fn test_other_event() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = write_event(&tmp_dir, "{}");
+        assert_eq!(
+            GithubEvent::from_file("schedule", &path).unwrap(),
+            GithubEvent::Other("schedule".to_string())
+        );
+
+        let path = write_event(&tmp_dir, "not json");
+        assert!(matches!(
+            GithubEvent::from_file("push", &path).unwrap_err(),
+            ReadGithubEventError::Process(_)
+        ));
+
+        let path = write_event(&tmp_dir, r#"{"number": "twelve"}"#);
+        assert!(matches!(
+            GithubEvent::from_file("pull_request", &path).unwrap_err(),
+            ReadGithubEventError::Malformed { .. }
+        ));
+    }
+
+    #[test]
+    fn test_format_github_output() {
+        assert_eq!(format_github_output("foo", "bar"), "foo=bar\n");
+        assert_eq!(format_github_output("foo", ""), "foo=\n");
+
+        let output = format_github_output("foo", "line 1\nline 2");
+        let (first_line, rest) = output.split_once('\n').unwrap();
+        let delimiter = first_line.strip_prefix("foo<<").unwrap();
+        assert!(delimiter.starts_with("ghadelimiter_"));
+        assert_eq!(rest, format!("line 1\nline 2\n{delimiter}\n"));
+    }
+}
@@ -8,11 +8,16 @@
 
 //! Utilities for running `git` commands.
 
-use crate::cmd::{RunCommandError, get_cmd_stdout_utf8, run_cmd};
+use crate::cmd::{
+    RunCommandError, format_cmd, get_cmd_output, get_cmd_stdout,
+    get_cmd_stdout_utf8, run_cmd, run_with_input,
+};
+use std::collections::BTreeMap;
 use std::ffi::OsStr;
 use std::fmt::{self, Display, Formatter};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
 use std::{env, io};
 
 /// Error returned by [`Repo::open`] and [`Repo::open_path`].
@@ -21,17 +26,43 @@ pub enum RepoOpenError {
     /// Failed to get current directory.
     CurrentDir(io::Error),
 
+    /// The path does not exist or is not a directory.
+    NotADirectory(PathBuf),
+
+    /// Neither the path nor any of its parents is in a git repository.
+    NotARepository(PathBuf),
+
+    /// Failed to run git.
+    Process(RunCommandError),
+
     /// The directory does not have a `.git` subdirectory.
+    ///
+    /// This is no longer returned; repos are now discovered the same
+    /// way git does it, see [`RepoOpenError::NotARepository`].
+    #[deprecated(note = "no longer returned, use `NotARepository` instead")]
     GitDirMissing(PathBuf),
 }
 
 impl Display for RepoOpenError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "failed to open git repo: ")?;
+        #[allow(deprecated)]
         match self {
             Self::CurrentDir(err) => {
                 write!(f, "failed to get current dir: {err}")
             }
+            Self::NotADirectory(path) => {
+                write!(f, "{} is not a directory", path.display())
+            }
+            Self::NotARepository(path) => {
+                write!(
+                    f,
+                    "{} is not in a git repository (or any of the parent \
+                     directories)",
+                    path.display()
+                )
+            }
+            Self::Process(err) => write!(f, "{err}"),
             Self::GitDirMissing(path) => {
                 write!(f, "{} does not exist", path.display())
             }
@@ -39,17 +70,384 @@ impl Display for RepoOpenError {
     }
 }
 
-impl std::error::Error for RepoOpenError {}
+impl std::error::Error for RepoOpenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        #[allow(deprecated)]
+        match self {
+            Self::CurrentDir(err) => Some(err),
+            Self::Process(err) => Some(err),
+            Self::NotADirectory(_)
+            | Self::NotARepository(_)
+            | Self::GitDirMissing(_) => None,
+        }
+    }
+}
+
+/// Name and email used to identify the author, committer, or tagger.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Identity {
+    /// Name, e.g. `Jane Doe`.
+    pub name: String,
+
+    /// Email address, e.g. `jane@example.com`.
+    pub email: String,
+}
+
+impl Identity {
+    /// Create an `Identity`.
+    pub fn new<S1, S2>(name: S1, email: S2) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            email: email.into(),
+        }
+    }
+
+    /// The identity GitHub uses for commits made by GitHub Actions.
+    pub fn github_actions_bot() -> Self {
+        Self::new(
+            "github-actions[bot]",
+            "41898282+github-actions[bot]@users.noreply.github.com",
+        )
+    }
+
+    /// Parse an identity in the form `Name <email>`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (name, email) = s.trim().strip_suffix('>')?.split_once('<')?;
+        let name = name.trim();
+        let email = email.trim();
+        if name.is_empty() || email.is_empty() {
+            return None;
+        }
+        Some(Self::new(name, email))
+    }
+}
+
+impl Display for Identity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} <{}>", self.name, self.email)
+    }
+}
+
+/// Format of a tag signature.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum SigningFormat {
+    /// Sign with GPG.
+    #[default]
+    Gpg,
+
+    /// Sign with an SSH key.
+    Ssh,
+}
+
+impl FromStr for SigningFormat {
+    type Err = ParseSigningFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gpg" | "openpgp" => Ok(Self::Gpg),
+            "ssh" => Ok(Self::Ssh),
+            _ => Err(ParseSigningFormatError(s.to_string())),
+        }
+    }
+}
+
+/// Error returned when parsing an unknown [`SigningFormat`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseSigningFormatError(pub String);
+
+impl Display for ParseSigningFormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid signing format \"{}\", expected \"gpg\" or \"ssh\"",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseSigningFormatError {}
+
+/// How to sign a tag.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct TagSigning {
+    /// Signature format.
+    pub format: SigningFormat,
+
+    /// Key to sign with. For GPG this is a key ID, for SSH it is the
+    /// path of a private key file (or a public key whose private key is
+    /// loaded in `ssh-agent`). If `None`, git's default signing key is
+    /// used.
+    pub key: Option<String>,
+}
+
+impl TagSigning {
+    /// Get signing options from the environment.
+    ///
+    /// The key is read from `RELEASE_UTILS_SIGNING_KEY`, and the format
+    /// from `RELEASE_UTILS_SIGNING_FORMAT` (`gpg` or `ssh`, defaulting
+    /// to `gpg`). Returns `None` if no key is set.
+    pub fn from_env() -> Result<Option<Self>, ParseSigningFormatError> {
+        let var = |name| env::var(name).ok().filter(|v| !v.is_empty());
+
+        let Some(key) = var("RELEASE_UTILS_SIGNING_KEY") else {
+            return Ok(None);
+        };
+        let format = match var("RELEASE_UTILS_SIGNING_FORMAT") {
+            Some(format) => format.parse()?,
+            None => SigningFormat::default(),
+        };
+        Ok(Some(Self {
+            format,
+            key: Some(key),
+        }))
+    }
+}
+
+/// A ref the remote refused to update, see [`PushTagsError`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct RejectedRef {
+    /// Full name of the ref, e.g. `refs/tags/foo-v1.0.0`.
+    pub git_ref: String,
+
+    /// Reason given by git, e.g. `[remote rejected] (pre-receive hook
+    /// declined)`.
+    pub reason: String,
+}
+
+/// Error returned by [`Repo::push_git_tags`].
+#[derive(Debug)]
+pub enum PushTagsError {
+    /// Failed to run git.
+    Process(RunCommandError),
+
+    /// The push was rejected. Since the push is atomic, none of the
+    /// tags were pushed.
+    Rejected(Vec<RejectedRef>),
+}
+
+impl Display for PushTagsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Process(err) => write!(f, "failed to push tags: {err}"),
+            Self::Rejected(refs) => {
+                write!(f, "push was rejected, no tags were pushed:")?;
+                for r in refs {
+                    write!(f, "\n  {}: {}", r.git_ref, r.reason)?;
+                }
+                if refs.iter().any(|r| r.reason.contains("remote rejected")) {
+                    write!(
+                        f,
+                        "\nthe remote refused the push; check whether tag \
+                         protection rules allow creating these tags"
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for PushTagsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Process(err) => Some(err),
+            Self::Rejected(_) => None,
+        }
+    }
+}
+
+/// Error returned by [`Repo::push_branch`].
+#[derive(Debug)]
+pub enum PushBranchError {
+    /// Failed to run git.
+    Process(RunCommandError),
+
+    /// The push was rejected, e.g. because it wasn't a fast-forward or
+    /// the remote branch didn't match the lease.
+    Rejected(RejectedRef),
+}
+
+impl Display for PushBranchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Process(err) => write!(f, "failed to push branch: {err}"),
+            Self::Rejected(r) => {
+                write!(f, "push of {} was rejected: {}", r.git_ref, r.reason)?;
+                if r.reason.contains("stale info") {
+                    write!(
+                        f,
+                        "\nthe remote branch is not at the expected commit"
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for PushBranchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Process(err) => Some(err),
+            Self::Rejected(_) => None,
+        }
+    }
+}
+
+/// Options for [`Repo::push_branch`].
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct PushBranchOptions {
+    /// Allow overwriting the remote branch with a non-fast-forward
+    /// push, as long as it hasn't changed unexpectedly. This uses
+    /// `git push --force-with-lease`.
+    pub force_with_lease: bool,
+
+    /// SHA the remote branch is expected to be at when
+    /// `force_with_lease` is set. If `None`, the remote-tracking branch
+    /// is used as the expected value.
+    pub expected_sha: Option<String>,
+}
+
+/// History that [`Repo::ensure_history`] should make available.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum History {
+    /// All history. Shallow repos are unshallowed.
+    Full,
+
+    /// If the repo is shallow, deepen it by this many commits.
+    Deepen(u32),
+
+    /// The given commits (or other revisions resolvable by the remote,
+    /// such as tags) must be present. Missing ones are fetched.
+    Commits(Vec<String>),
+}
+
+/// An operation that has been started in the work tree but not yet
+/// finished, see [`Repo::get_in_progress_operation`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum InProgressOperation {
+    /// A merge with unresolved conflicts or an uncommitted result.
+    Merge,
+
+    /// A rebase, including `git am`.
+    Rebase,
+
+    /// A cherry-pick.
+    CherryPick,
+
+    /// A revert.
+    Revert,
+}
+
+impl Display for InProgressOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Merge => "merge",
+            Self::Rebase => "rebase",
+            Self::CherryPick => "cherry-pick",
+            Self::Revert => "revert",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// A commit, as returned by [`Repo::commits`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Commit {
+    /// Full SHA of the commit.
+    pub sha: String,
+
+    /// Full SHAs of the parent commits.
+    pub parents: Vec<String>,
+
+    /// Author of the commit.
+    pub author: Identity,
+
+    /// Committer of the commit.
+    pub committer: Identity,
+
+    /// Author date in strict ISO 8601 format, e.g.
+    /// `2024-01-02T03:04:05+00:00`.
+    pub date: String,
+
+    /// First paragraph of the commit message, joined into one line.
+    pub subject: String,
+
+    /// Rest of the commit message, including any trailers.
+    pub body: String,
+
+    /// Trailers at the end of the commit message, in order.
+    pub trailers: Vec<Trailer>,
+}
+
+/// A commit message trailer, e.g. `Signed-off-by: Jane <jane@example.com>`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Trailer {
+    /// Trailer key, e.g. `Signed-off-by`.
+    pub key: String,
+
+    /// Trailer value. Multi-line values are unfolded into one line.
+    pub value: String,
+}
+
+impl Trailer {
+    /// Check if the trailer's key is `key`. Like in git, keys are
+    /// compared case-insensitively.
+    pub fn has_key(&self, key: &str) -> bool {
+        self.key.eq_ignore_ascii_case(key)
+    }
+}
+
+/// Default remote used when a `Repo` method needs to fetch missing
+/// history, see [`Repo::set_history_remote`].
+const DEFAULT_HISTORY_REMOTE: &str = "origin";
+
+/// Number of commits fetched at first when checking if a commit is in a
+/// remote branch of a shallow repo. Each further fetch deepens by twice
+/// as many commits as the previous one.
+const BRANCH_DEEPEN_STEP: u32 = 50;
+
+/// Options for [`Repo::make_git_tag`].
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct TagOptions {
+    /// Message for an annotated tag. If `None` and the tag is not
+    /// signed, a lightweight tag is created.
+    pub message: Option<String>,
+
+    /// Sign the tag. Signed tags are always annotated; if there is no
+    /// message the tag name is used as the message.
+    pub signing: Option<TagSigning>,
+
+    /// Identity of the tagger. If `None`, git's configured committer
+    /// identity is used.
+    pub tagger: Option<Identity>,
+}
 
 /// Git repo.
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct Repo(PathBuf);
+pub struct Repo {
+    /// Root of the work tree, or the git directory for repos without
+    /// a work tree.
+    path: PathBuf,
+
+    /// Absolute path of the git directory.
+    git_dir: PathBuf,
+
+    /// Whether the repo has a work tree.
+    has_work_tree: bool,
+
+    /// Remote to fetch missing history from.
+    history_remote: String,
+}
 
 impl Repo {
     /// Get a `Repo` for the current directory.
     ///
-    /// This will fail if the current directory does not contain a
-    /// `.git` subdirectory.
+    /// See [`Repo::open_path`] for details.
     pub fn open() -> Result<Self, RepoOpenError> {
         let path = env::current_dir().map_err(RepoOpenError::CurrentDir)?;
         Self::open_path(path)
@@ -57,33 +455,103 @@ impl Repo {
 
     /// Get a `Repo` for the given path.
     ///
-    /// This will fail if the `path` does not contain a `.git`
-    /// subdirectory.
+    /// The repo is discovered the same way git does it: `path` may be
+    /// anywhere within the work tree, and parent directories are
+    /// searched. Worktrees and submodules (where `.git` is a file
+    /// rather than a directory) and bare repos are supported, as are
+    /// git's env vars such as `GIT_DIR`.
     pub fn open_path<P>(path: P) -> Result<Self, RepoOpenError>
     where
         P: Into<PathBuf>,
     {
         let path = path.into();
-
-        // Check that this is a git repo. This is just to help fail
-        // quickly if the path is wrong; it isn't checking that the git
-        // repo is valid or anything.
-        //
-        // Also, there are various special types of git checkouts so
-        // it's quite possible this check is wrong for special
-        // circumstances, but for a typical CI release process it should
-        // be fine.
-        let git_dir = path.join(".git");
-        if !git_dir.exists() {
-            return Err(RepoOpenError::GitDirMissing(git_dir));
+        if !path.is_dir() {
+            return Err(RepoOpenError::NotADirectory(path));
         }
 
-        Ok(Self(path))
+        let rev_parse = |args: &[&str]| {
+            let mut cmd = Command::new("git");
+            // The error message is checked below, so make sure it isn't
+            // translated.
+            cmd.env("LC_ALL", "C");
+            cmd.arg("-C");
+            cmd.arg(&path);
+            cmd.arg("rev-parse");
+            cmd.args(args);
+            let cmd_str = format_cmd(&cmd);
+            let output = get_cmd_output(cmd).map_err(RepoOpenError::Process)?;
+            if output.status.success() {
+                String::from_utf8(output.stdout).map_err(|err| {
+                    RepoOpenError::Process(RunCommandError::NonUtf8 {
+                        cmd: cmd_str,
+                        err,
+                    })
+                })
+            } else if String::from_utf8_lossy(&output.stderr)
+                .contains("not a git repository")
+            {
+                Err(RepoOpenError::NotARepository(path.clone()))
+            } else {
+                Err(RepoOpenError::Process(RunCommandError::NonZeroExit {
+                    cmd: cmd_str,
+                    status: output.status,
+                }))
+            }
+        };
+
+        let output =
+            rev_parse(&["--is-inside-work-tree", "--absolute-git-dir"])?;
+        let mut lines = output.lines();
+        let has_work_tree = lines.next() == Some("true");
+        let git_dir = PathBuf::from(lines.next().unwrap_or_default());
+
+        // `--show-toplevel` fails if there's no work tree, e.g. in a
+        // bare repo.
+        let path = if has_work_tree {
+            PathBuf::from(rev_parse(&["--show-toplevel"])?.trim_end())
+        } else {
+            git_dir.clone()
+        };
+
+        Ok(Self {
+            path,
+            git_dir,
+            has_work_tree,
+            history_remote: DEFAULT_HISTORY_REMOTE.to_string(),
+        })
     }
 
     /// Get the repo path.
+    ///
+    /// This is the root of the work tree, or the git directory if the
+    /// repo has no work tree (e.g. a bare repo).
     pub fn path(&self) -> &Path {
-        &self.0
+        &self.path
+    }
+
+    /// Get the absolute path of the git directory.
+    ///
+    /// For a worktree or submodule this is the directory that `.git`
+    /// points to.
+    pub fn git_dir(&self) -> &Path {
+        &self.git_dir
+    }
+
+    /// Get the remote that `Repo` methods fetch missing history from.
+    pub fn history_remote(&self) -> &str {
+        &self.history_remote
+    }
+
+    /// Set the remote that `Repo` methods fetch missing history from,
+    /// e.g. when walking the history of a shallow clone. Defaults to
+    /// `origin`.
+    pub fn set_history_remote<S: Into<String>>(&mut self, remote: S) {
+        self.history_remote = remote.into();
+    }
+
+    /// Whether the repo has a work tree. This is false for bare repos.
+    pub fn has_work_tree(&self) -> bool {
+        self.has_work_tree
     }
 
     /// Create a git command with the given args.
@@ -99,11 +567,454 @@ impl Repo {
         cmd
     }
 
+    /// Check if the repo is a shallow clone.
+    ///
+    /// `actions/checkout` creates shallow clones with a depth of one by
+    /// default.
+    pub fn is_shallow(&self) -> Result<bool, RunCommandError> {
+        let cmd =
+            self.get_git_command(["rev-parse", "--is-shallow-repository"]);
+        let output = get_cmd_stdout_utf8(cmd)?;
+        Ok(output.trim() == "true")
+    }
+
+    /// Make sure the requested `history` is available locally,
+    /// fetching from `remote` if needed.
+    ///
+    /// `Repo` methods that walk history call this automatically, using
+    /// the [history remote](Self::history_remote).
+    pub fn ensure_history(
+        &self,
+        remote: &str,
+        history: &History,
+    ) -> Result<(), RunCommandError> {
+        match history {
+            History::Full => {
+                if self.is_shallow()? {
+                    let cmd = self.get_git_command([
+                        "fetch",
+                        "--unshallow",
+                        "--tags",
+                        remote,
+                    ]);
+                    run_cmd(cmd)?;
+                }
+            }
+            History::Deepen(depth) => {
+                if self.is_shallow()? {
+                    let cmd = self.get_git_command([
+                        "fetch".to_string(),
+                        format!("--deepen={depth}"),
+                        remote.to_string(),
+                    ]);
+                    run_cmd(cmd)?;
+                }
+            }
+            History::Commits(commits) => {
+                let mut missing = Vec::new();
+                for commit in commits {
+                    if !self.has_commit(commit)? {
+                        missing.push(commit.as_str());
+                    }
+                }
+                if !missing.is_empty() {
+                    let mut cmd =
+                        self.get_git_command(["fetch", "--no-tags", remote]);
+                    cmd.args(missing);
+                    run_cmd(cmd)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Make sure the history of `range` is present. Shallow repos are
+    /// only unshallowed if walking the range reaches the shallow
+    /// boundary, or an end of the range isn't present locally.
+    fn ensure_range_history(&self, range: &str) -> Result<(), RunCommandError> {
+        if !self.is_shallow()? {
+            return Ok(());
+        }
+        if !self.is_range_complete(range)? {
+            self.ensure_history(&self.history_remote, &History::Full)?;
+        }
+        Ok(())
+    }
+
+    /// Check if both ends of `range` and all of the commits between
+    /// them are present locally.
+    fn is_range_complete(&self, range: &str) -> Result<bool, RunCommandError> {
+        let ends = range
+            .split_once("...")
+            .or_else(|| range.split_once(".."))
+            .map_or(vec![range], |(from, to)| vec![from, to]);
+        for end in ends {
+            let end = end.trim_start_matches('^');
+            if !self.has_commit(if end.is_empty() { "HEAD" } else { end })? {
+                return Ok(false);
+            }
+        }
+
+        // Commits on the shallow boundary have their parents hidden, so
+        // they look like root commits when walking history. The raw
+        // commit object still lists the parents.
+        let cmd = self.get_git_command(["rev-list", "--parents", range, "--"]);
+        for line in get_cmd_stdout_utf8(cmd)?.lines() {
+            if line.contains(' ') {
+                continue;
+            }
+            // Commit messages are not necessarily UTF-8.
+            let cmd = self.get_git_command(["cat-file", "commit", line]);
+            let raw = get_cmd_stdout(cmd)?;
+            let has_parents = raw
+                .split(|b| *b == b'\n')
+                .take_while(|line| !line.is_empty())
+                .any(|line| line.starts_with(b"parent "));
+            if has_parents {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Check if `rev` resolves to a commit that is present locally.
+    fn has_commit(&self, rev: &str) -> Result<bool, RunCommandError> {
+        let cmd = self.get_git_command([
+            "cat-file".to_string(),
+            "-e".to_string(),
+            format!("{rev}^{{commit}}"),
+        ]);
+        Ok(get_cmd_output(cmd)?.status.success())
+    }
+
+    /// Get the SHA of the `HEAD` commit.
+    pub fn head_sha(&self) -> Result<String, RunCommandError> {
+        let cmd = self.get_git_command(["rev-parse", "--verify", "HEAD"]);
+        let output = get_cmd_stdout_utf8(cmd)?;
+        Ok(output.trim().to_string())
+    }
+
+    /// Get the paths of files with uncommitted changes, including
+    /// untracked files. Ignored files are not included.
+    ///
+    /// An empty list means the work tree is clean. For renamed and
+    /// copied files, the new path is returned.
+    pub fn get_uncommitted_changes(
+        &self,
+    ) -> Result<Vec<String>, RunCommandError> {
+        let cmd = self.get_git_command(["status", "--porcelain", "-z"]);
+        let output = get_cmd_stdout_utf8(cmd)?;
+        // Each record is a two-character status code, a space, and the
+        // path, terminated by NUL. Paths are not quoted. Renames and
+        // copies are followed by a second record with the original
+        // path.
+        let mut records = output.split_terminator('\0');
+        let mut paths = Vec::new();
+        while let Some(record) = records.next() {
+            let (Some(status), Some(path)) = (record.get(..2), record.get(3..))
+            else {
+                continue;
+            };
+            if status.contains(['R', 'C']) {
+                records.next();
+            }
+            paths.push(path.to_string());
+        }
+        Ok(paths)
+    }
+
+    /// Check if a commit is contained in any remote-tracking branch.
+    ///
+    /// This is based on the remote-tracking branches as of the last
+    /// fetch. Shallow repos are unshallowed first, since the check
+    /// needs the full history.
+    pub fn is_commit_pushed(
+        &self,
+        commit_sha: &str,
+    ) -> Result<bool, RunCommandError> {
+        self.ensure_history(&self.history_remote, &History::Full)?;
+        let cmd = self.get_git_command([
+            "branch",
+            "--remotes",
+            "--contains",
+            commit_sha,
+        ]);
+        let output = get_cmd_stdout_utf8(cmd)?;
+        Ok(!output.trim().is_empty())
+    }
+
+    /// Get the URL of `remote`.
+    pub fn get_remote_url(
+        &self,
+        remote: &str,
+    ) -> Result<String, RunCommandError> {
+        let cmd = self.get_git_command(["remote", "get-url", remote]);
+        let output = get_cmd_stdout_utf8(cmd)?;
+        Ok(output.trim().to_string())
+    }
+
+    /// Get the name of `remote`'s default branch, e.g. `main`.
+    ///
+    /// This queries the remote, so it reflects the current default
+    /// branch even if `refs/remotes/<remote>/HEAD` is missing or stale.
+    /// Returns `None` if the remote's `HEAD` doesn't point at a branch,
+    /// e.g. because the remote is empty.
+    pub fn get_default_branch(
+        &self,
+        remote: &str,
+    ) -> Result<Option<String>, RunCommandError> {
+        let cmd = self.get_git_command([
+            "ls-remote",
+            "--symref",
+            "--",
+            remote,
+            "HEAD",
+        ]);
+        let output = get_cmd_stdout_utf8(cmd)?;
+        // The symref is reported on a line of the form
+        // `ref: refs/heads/<branch>\tHEAD`.
+        Ok(output
+            .lines()
+            .filter_map(|line| line.strip_prefix("ref: refs/heads/"))
+            .find_map(|line| line.strip_suffix("\tHEAD"))
+            .map(str::to_string))
+    }
+
+    /// Check if a commit is contained in `branch` on `remote`.
+    ///
+    /// The branch is fetched first, so this reflects the current state
+    /// of the remote. In a shallow repo, the branch's history is
+    /// deepened a step at a time until the commit is found or the
+    /// branch's full history has been fetched.
+    pub fn is_commit_in_remote_branch(
+        &self,
+        commit_sha: &str,
+        remote: &str,
+        branch: &str,
+    ) -> Result<bool, RunCommandError> {
+        self.ensure_history(
+            remote,
+            &History::Commits(vec![commit_sha.to_string()]),
+        )?;
+        let tracking_ref = format!("refs/remotes/{remote}/{branch}");
+        let refspec = format!("+refs/heads/{branch}:{tracking_ref}");
+        let shallow = self.is_shallow()?;
+
+        let mut cmd = self.get_git_command(["fetch", "--no-tags"]);
+        if shallow {
+            cmd.arg(format!("--depth={BRANCH_DEEPEN_STEP}"));
+        }
+        cmd.args([remote, &refspec]);
+        run_cmd(cmd)?;
+
+        let mut step = BRANCH_DEEPEN_STEP;
+        loop {
+            if self.is_ancestor(commit_sha, &tracking_ref)? {
+                return Ok(true);
+            }
+            if !shallow {
+                return Ok(false);
+            }
+
+            // If deepening doesn't add any commits, the branch's full
+            // history is already present.
+            let count = self.count_commits(&tracking_ref)?;
+            let cmd = self.get_git_command([
+                "fetch".to_string(),
+                "--no-tags".to_string(),
+                format!("--deepen={step}"),
+                remote.to_string(),
+                refspec.clone(),
+            ]);
+            run_cmd(cmd)?;
+            if self.count_commits(&tracking_ref)? == count {
+                return Ok(false);
+            }
+            step = step.saturating_mul(2);
+        }
+    }
+
+    /// Check if `commit` is an ancestor of (or the same as) `rev`.
+    fn is_ancestor(
+        &self,
+        commit: &str,
+        rev: &str,
+    ) -> Result<bool, RunCommandError> {
+        let cmd =
+            self.get_git_command(["merge-base", "--is-ancestor", commit, rev]);
+        let cmd_str = format_cmd(&cmd);
+        let output = get_cmd_output(cmd)?;
+        // Exit code 1 means the commit isn't an ancestor, anything else
+        // is an error.
+        match output.status.code() {
+            Some(0) => Ok(true),
+            Some(1) => Ok(false),
+            _ => Err(RunCommandError::NonZeroExit {
+                cmd: cmd_str,
+                status: output.status,
+            }),
+        }
+    }
+
+    /// Count the commits reachable from `rev` that are present
+    /// locally.
+    fn count_commits(&self, rev: &str) -> Result<u64, RunCommandError> {
+        let cmd = self.get_git_command(["rev-list", "--count", rev]);
+        let output = get_cmd_stdout_utf8(cmd)?;
+        // git always prints a number here.
+        Ok(output.trim().parse().unwrap_or(0))
+    }
+
+    /// Get the operation in progress in the work tree, if any, e.g. a
+    /// merge with unresolved conflicts.
+    pub fn get_in_progress_operation(&self) -> Option<InProgressOperation> {
+        let git_dir = self.git_dir();
+        if git_dir.join("rebase-merge").exists()
+            || git_dir.join("rebase-apply").exists()
+        {
+            Some(InProgressOperation::Rebase)
+        } else if git_dir.join("MERGE_HEAD").exists() {
+            Some(InProgressOperation::Merge)
+        } else if git_dir.join("CHERRY_PICK_HEAD").exists() {
+            Some(InProgressOperation::CherryPick)
+        } else if git_dir.join("REVERT_HEAD").exists() {
+            Some(InProgressOperation::Revert)
+        } else {
+            None
+        }
+    }
+
+    /// Get the commits in `range`, newest first.
+    ///
+    /// The range can be anything `git log` accepts, e.g. a single
+    /// revision (that commit and all its ancestors) or
+    /// `foo-v1.0.0..HEAD`. If `paths` is not empty, only commits that
+    /// touch at least one of the paths are included.
+    ///
+    /// Shallow repos are unshallowed first if the range extends beyond
+    /// the shallow history.
+    pub fn commits(
+        &self,
+        range: &str,
+        paths: &[&Path],
+    ) -> Result<Vec<Commit>, RunCommandError> {
+        self.ensure_range_history(range)?;
+
+        // Fields are separated by the ASCII unit separator, and commits
+        // by the record separator.
+        let format = [
+            "%H",
+            "%P",
+            "%an",
+            "%ae",
+            "%cn",
+            "%ce",
+            "%aI",
+            "%s",
+            "%b",
+            "%(trailers:only,unfold)",
+        ]
+        .join("%x1f");
+        let mut cmd = self.get_git_command([
+            "log".to_string(),
+            format!("--format=format:{format}%x1e"),
+            range.to_string(),
+        ]);
+        if !paths.is_empty() {
+            cmd.arg("--");
+            cmd.args(paths);
+        }
+        let output = get_cmd_stdout_utf8(cmd)?;
+
+        Ok(output
+            .split('\x1e')
+            .filter_map(parse_commit_record)
+            .collect())
+    }
+
+    /// Get the local tags matching the glob `pattern`, e.g. `foo-v*`.
+    pub fn list_tags(
+        &self,
+        pattern: &str,
+    ) -> Result<Vec<String>, RunCommandError> {
+        let cmd = self.get_git_command(["tag", "--list", pattern]);
+        let output = get_cmd_stdout_utf8(cmd)?;
+        Ok(output.lines().map(str::to_string).collect())
+    }
+
+    /// Get the most recent tag matching the glob `pattern` that is
+    /// reachable from `rev`, or `None` if there isn't one.
+    pub fn get_last_tag_matching(
+        &self,
+        pattern: &str,
+        rev: &str,
+    ) -> Result<Option<String>, RunCommandError> {
+        self.ensure_history(&self.history_remote, &History::Full)?;
+
+        let mut cmd = self.get_git_command([
+            "describe",
+            "--tags",
+            "--abbrev=0",
+            "--match",
+            pattern,
+            rev,
+        ]);
+        // The error message is checked below, so make sure it isn't
+        // translated.
+        cmd.env("LC_ALL", "C");
+        let cmd_str = format_cmd(&cmd);
+        let output = get_cmd_output(cmd)?;
+        if output.status.success() {
+            let tag = String::from_utf8(output.stdout).map_err(|err| {
+                RunCommandError::NonUtf8 { cmd: cmd_str, err }
+            })?;
+            return Ok(Some(tag.trim().to_string()));
+        }
+
+        // `git describe` fails if no tag matches.
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("No names found")
+            || stderr.contains("No tags can describe")
+        {
+            Ok(None)
+        } else {
+            Err(RunCommandError::NonZeroExit {
+                cmd: cmd_str,
+                status: output.status,
+            })
+        }
+    }
+
+    /// Get the paths of files that differ between the commits `from`
+    /// and `to`. Paths are relative to the root of the repo.
+    pub fn get_changed_files(
+        &self,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<PathBuf>, RunCommandError> {
+        self.ensure_history(
+            &self.history_remote,
+            &History::Commits(vec![from.to_string(), to.to_string()]),
+        )?;
+
+        let cmd = self.get_git_command(["diff", "--name-only", "-z", from, to]);
+        let output = get_cmd_stdout_utf8(cmd)?;
+        Ok(output
+            .split('\0')
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .collect())
+    }
+
     /// Get the subject of the commit message for the given commit.
     pub fn get_commit_message_body(
         &self,
         commit_sha: &str,
     ) -> Result<String, RunCommandError> {
+        self.ensure_history(
+            &self.history_remote,
+            &History::Commits(vec![commit_sha.to_string()]),
+        )?;
         let cmd = self.get_git_command([
             "log",
             "-1",
@@ -120,6 +1031,10 @@ impl Repo {
         &self,
         commit_sha: &str,
     ) -> Result<String, RunCommandError> {
+        self.ensure_history(
+            &self.history_remote,
+            &History::Commits(vec![commit_sha.to_string()]),
+        )?;
         let cmd = self.get_git_command([
             "log",
             "-1",
@@ -131,17 +1046,106 @@ impl Repo {
         Ok(output)
     }
 
-    /// Fetch git tags from the remote.
+    /// Get the trailers of the commit message for the given commit,
+    /// e.g. `Release: foo, bar`.
+    ///
+    /// Multi-line trailer values are unfolded into a single line.
+    pub fn get_commit_trailers(
+        &self,
+        commit_sha: &str,
+    ) -> Result<Vec<Trailer>, RunCommandError> {
+        self.ensure_history(
+            &self.history_remote,
+            &History::Commits(vec![commit_sha.to_string()]),
+        )?;
+        let cmd = self.get_git_command([
+            "log",
+            "-1",
+            "--format=format:%(trailers:only,unfold)",
+            commit_sha,
+        ]);
+        let output = get_cmd_stdout_utf8(cmd)?;
+        Ok(parse_trailer_lines(&output))
+    }
+
+    /// Parse the trailers of a commit `message` that may not be
+    /// committed, e.g. one from a CI event payload.
+    ///
+    /// This uses `git interpret-trailers`, so the repo's trailer config
+    /// (such as `trailer.separators`) is respected.
+    pub fn parse_trailers(
+        &self,
+        message: &str,
+    ) -> Result<Vec<Trailer>, RunCommandError> {
+        let cmd = self.get_git_command(["interpret-trailers", "--parse"]);
+        let output = run_with_input(cmd, message.as_bytes())?;
+        Ok(parse_trailer_lines(&output))
+    }
+
+    /// Fetch git tags from `origin`.
+    ///
+    /// See [`Repo::fetch_git_tags_from_remote`].
     pub fn fetch_git_tags(&self) -> Result<(), RunCommandError> {
-        let cmd = self.get_git_command(["fetch", "--tags"]);
+        self.fetch_git_tags_from_remote("origin")
+    }
+
+    /// Fetch git tags from `remote`.
+    ///
+    /// This fetches every tag, which can be slow in large repos. Use
+    /// [`Repo::remote_tags`] to check which tags exist on the remote, or
+    /// [`Repo::fetch_tags`] to fetch specific tags.
+    pub fn fetch_git_tags_from_remote(
+        &self,
+        remote: &str,
+    ) -> Result<(), RunCommandError> {
+        let cmd = self.get_git_command(["fetch", "--tags", remote]);
         run_cmd(cmd)?;
         Ok(())
     }
 
+    /// Fetch specific `tags` from `remote`.
+    ///
+    /// Local tags are never overwritten: if a local tag with the same
+    /// name points at a different object, the fetch fails.
+    pub fn fetch_tags(
+        &self,
+        remote: &str,
+        tags: &[&str],
+    ) -> Result<(), RunCommandError> {
+        let mut cmd = self.get_git_command(["fetch", "--no-tags", remote]);
+        cmd.args(
+            tags.iter()
+                .map(|tag| format!("refs/tags/{tag}:refs/tags/{tag}")),
+        );
+        run_cmd(cmd)
+    }
+
+    /// Get the tags on `remote` matching any of the glob `patterns`
+    /// (e.g. `foo-v*`), without fetching them.
+    ///
+    /// Returns a map from tag name to the SHA of the commit the tag
+    /// points at. Annotated tags are peeled to their commit. If
+    /// `patterns` is empty, all tags are returned.
+    pub fn remote_tags(
+        &self,
+        remote: &str,
+        patterns: &[&str],
+    ) -> Result<BTreeMap<String, String>, RunCommandError> {
+        let mut cmd =
+            self.get_git_command(["ls-remote", "--tags", "--", remote]);
+        cmd.args(
+            patterns
+                .iter()
+                .map(|pattern| format!("refs/tags/{pattern}")),
+        );
+        let output = get_cmd_stdout_utf8(cmd)?;
+        Ok(parse_ls_remote_tags(&output))
+    }
+
     /// Check if a git tag exists locally.
     ///
-    /// All git tags were fetched at the start of auto-release, so checking locally
-    /// is sufficient.
+    /// This only reflects the remote if the tag has been fetched, see
+    /// [`Repo::remote_tags`] for checking the remote directly.
     pub fn does_git_tag_exist(
         &self,
         tag: &str,
@@ -152,20 +1156,431 @@ impl Repo {
         Ok(output.lines().any(|line| line == tag))
     }
 
-    /// Create a git tag locally and push it.
+    /// Get the SHA of the commit a local tag points at, or `None` if the
+    /// tag doesn't exist.
+    ///
+    /// Annotated tags are peeled to the commit they point at.
+    pub fn get_tag_commit(
+        &self,
+        tag: &str,
+    ) -> Result<Option<String>, RunCommandError> {
+        let cmd = self.get_git_command([
+            "rev-parse".to_string(),
+            "--verify".to_string(),
+            "--quiet".to_string(),
+            format!("refs/tags/{tag}^{{commit}}"),
+        ]);
+        let output = get_cmd_output(cmd)?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let sha = String::from_utf8(output.stdout).map_err(|err| {
+            RunCommandError::NonUtf8 {
+                cmd: format!("git rev-parse refs/tags/{tag}"),
+                err,
+            }
+        })?;
+        Ok(Some(sha.trim().to_string()))
+    }
+
+    /// Delete `tag` locally, and from `remote` if set.
+    ///
+    /// Tags that don't exist are skipped, so deleting a tag again is
+    /// not an error.
+    pub fn delete_tag(
+        &self,
+        tag: &str,
+        remote: Option<&str>,
+    ) -> Result<(), RunCommandError> {
+        if let Some(remote) = remote {
+            if self.remote_tags(remote, &[tag])?.contains_key(tag) {
+                let cmd = self.get_git_command([
+                    "push".to_string(),
+                    remote.to_string(),
+                    format!(":refs/tags/{tag}"),
+                ]);
+                run_cmd(cmd)?;
+            }
+        }
+
+        if self.does_git_tag_exist(tag)? {
+            let cmd = self.get_git_command(["tag", "--delete", tag]);
+            run_cmd(cmd)?;
+        }
+        Ok(())
+    }
+
+    /// Create a lightweight git tag locally and push it to `origin`.
     pub fn make_and_push_git_tag(
         &self,
         tag: &str,
         commit_sha: &str,
     ) -> Result<(), RunCommandError> {
-        // Create the tag.
-        let cmd = self.get_git_command(["tag", tag, commit_sha]);
-        run_cmd(cmd)?;
+        self.make_and_push_git_tag_to_remote(tag, commit_sha, "origin")
+    }
 
-        // Push it.
-        let cmd = self.get_git_command(["push", "--tags"]);
-        run_cmd(cmd)?;
+    /// Create a lightweight git tag locally and push it to `remote`.
+    pub fn make_and_push_git_tag_to_remote(
+        &self,
+        tag: &str,
+        commit_sha: &str,
+        remote: &str,
+    ) -> Result<(), RunCommandError> {
+        self.make_git_tag(tag, commit_sha, &TagOptions::default())?;
+
+        let cmd = self.get_git_command([
+            "push".to_string(),
+            remote.to_string(),
+            format!("refs/tags/{tag}"),
+        ]);
+        run_cmd(cmd)
+    }
+
+    /// Create a git tag locally.
+    pub fn make_git_tag(
+        &self,
+        tag: &str,
+        commit_sha: &str,
+        opt: &TagOptions,
+    ) -> Result<(), RunCommandError> {
+        let mut cmd = self.get_git_command::<_, &str>([]);
+
+        if let Some(signing) = &opt.signing {
+            let format = match signing.format {
+                SigningFormat::Gpg => "openpgp",
+                SigningFormat::Ssh => "ssh",
+            };
+            cmd.args(["-c", &format!("gpg.format={format}")]);
+        }
+
+        cmd.arg("tag");
+        match &opt.signing {
+            Some(TagSigning { key: Some(key), .. }) => {
+                cmd.args(["--local-user", key]);
+            }
+            Some(TagSigning { key: None, .. }) => {
+                cmd.arg("--sign");
+            }
+            None => {
+                // Don't let a `tag.gpgSign` config sign the tag
+                // unexpectedly.
+                cmd.arg("--no-sign");
+            }
+        }
+
+        if opt.message.is_some() || opt.signing.is_some() {
+            let message = opt.message.as_deref().unwrap_or(tag);
+            cmd.args(["--annotate", "--message", message]);
+        }
+
+        if let Some(tagger) = &opt.tagger {
+            cmd.env("GIT_COMMITTER_NAME", &tagger.name);
+            cmd.env("GIT_COMMITTER_EMAIL", &tagger.email);
+        }
+
+        cmd.args([tag, commit_sha]);
+        run_cmd(cmd)
+    }
 
+    /// Append `text` to the note on `commit_sha` in `notes_ref`, e.g.
+    /// `refs/notes/releases`. The note is created if it doesn't exist.
+    ///
+    /// Appended text is separated from the existing note by a blank
+    /// line.
+    pub fn append_note(
+        &self,
+        notes_ref: &str,
+        commit_sha: &str,
+        text: &str,
+    ) -> Result<(), RunCommandError> {
+        let cmd = self.get_git_command([
+            "notes".to_string(),
+            format!("--ref={notes_ref}"),
+            "append".to_string(),
+            "--file=-".to_string(),
+            commit_sha.to_string(),
+        ]);
+        run_with_input(cmd, text.as_bytes())?;
         Ok(())
     }
+
+    /// Get the note on `commit_sha` in `notes_ref`, or `None` if the
+    /// commit has no note.
+    pub fn get_note(
+        &self,
+        notes_ref: &str,
+        commit_sha: &str,
+    ) -> Result<Option<String>, RunCommandError> {
+        // Check for the note first, since `git notes show` fails
+        // without a note.
+        let cmd = self.get_git_command([
+            "notes".to_string(),
+            format!("--ref={notes_ref}"),
+            "list".to_string(),
+            commit_sha.to_string(),
+        ]);
+        if !get_cmd_output(cmd)?.status.success() {
+            return Ok(None);
+        }
+
+        let cmd = self.get_git_command([
+            "notes".to_string(),
+            format!("--ref={notes_ref}"),
+            "show".to_string(),
+            commit_sha.to_string(),
+        ]);
+        Ok(Some(get_cmd_stdout_utf8(cmd)?))
+    }
+
+    /// Fetch `notes_ref` from `remote`, replacing the local notes.
+    ///
+    /// This should be done before appending notes that will be pushed,
+    /// so that the push is a fast-forward. It is not an error if the
+    /// remote doesn't have the notes ref yet.
+    pub fn fetch_notes(
+        &self,
+        remote: &str,
+        notes_ref: &str,
+    ) -> Result<(), RunCommandError> {
+        // Check that the remote has the notes ref first, since fetching
+        // a missing ref is an error.
+        let cmd = self.get_git_command(["ls-remote", "--", remote, notes_ref]);
+        let output = get_cmd_stdout_utf8(cmd)?;
+        let suffix = format!("\t{notes_ref}");
+        if !output.lines().any(|line| line.ends_with(&suffix)) {
+            return Ok(());
+        }
+
+        let cmd = self.get_git_command([
+            "fetch".to_string(),
+            "--no-tags".to_string(),
+            remote.to_string(),
+            format!("+{notes_ref}:{notes_ref}"),
+        ]);
+        run_cmd(cmd)
+    }
+
+    /// Push `notes_ref` to `remote`.
+    pub fn push_notes(
+        &self,
+        remote: &str,
+        notes_ref: &str,
+    ) -> Result<(), RunCommandError> {
+        let cmd = self.get_git_command([
+            "push".to_string(),
+            remote.to_string(),
+            format!("{notes_ref}:{notes_ref}"),
+        ]);
+        run_cmd(cmd)
+    }
+
+    /// Stage `paths` for the next commit, see [`Repo::commit`].
+    ///
+    /// Relative paths are relative to the root of the repo.
+    pub fn stage_files(&self, paths: &[&Path]) -> Result<(), RunCommandError> {
+        let mut cmd = self.get_git_command(["add", "--"]);
+        cmd.args(paths);
+        run_cmd(cmd)
+    }
+
+    /// Commit the staged changes with `message`, and get the SHA of the
+    /// new commit.
+    ///
+    /// The commit is made on top of `HEAD`, which may be detached (as
+    /// in a CI checkout); use [`Repo::create_branch`] to give it a
+    /// branch. If `identity` is set it is used as both the author and
+    /// the committer, otherwise git's configured identity is used.
+    pub fn commit(
+        &self,
+        message: &str,
+        identity: Option<&Identity>,
+    ) -> Result<String, RunCommandError> {
+        let mut cmd = self.get_git_command(["commit", "--file=-"]);
+        if let Some(identity) = identity {
+            cmd.env("GIT_AUTHOR_NAME", &identity.name);
+            cmd.env("GIT_AUTHOR_EMAIL", &identity.email);
+            cmd.env("GIT_COMMITTER_NAME", &identity.name);
+            cmd.env("GIT_COMMITTER_EMAIL", &identity.email);
+        }
+        run_with_input(cmd, message.as_bytes())?;
+        self.head_sha()
+    }
+
+    /// Create a local branch `name` pointing at `start_point`, e.g. a
+    /// commit SHA or `HEAD`. The branch is not checked out.
+    pub fn create_branch(
+        &self,
+        name: &str,
+        start_point: &str,
+    ) -> Result<(), RunCommandError> {
+        let cmd = self.get_git_command(["branch", name, start_point]);
+        run_cmd(cmd)
+    }
+
+    /// Push the local branch `branch` to the branch of the same name on
+    /// `remote`.
+    pub fn push_branch(
+        &self,
+        remote: &str,
+        branch: &str,
+        opt: &PushBranchOptions,
+    ) -> Result<(), PushBranchError> {
+        let git_ref = format!("refs/heads/{branch}");
+        let mut cmd = self.get_git_command(["push", "--porcelain"]);
+        if opt.force_with_lease {
+            let lease = match &opt.expected_sha {
+                Some(sha) => format!("--force-with-lease={git_ref}:{sha}"),
+                None => format!("--force-with-lease={git_ref}"),
+            };
+            cmd.arg(lease);
+        }
+        cmd.args([remote, &format!("{git_ref}:{git_ref}")]);
+
+        match run_push(cmd).map_err(PushBranchError::Process)? {
+            Ok(()) => Ok(()),
+            // Only one ref is pushed, so there is only one rejection.
+            Err(mut rejected) => {
+                Err(PushBranchError::Rejected(rejected.swap_remove(0)))
+            }
+        }
+    }
+
+    /// Push `tags` to `remote`.
+    ///
+    /// The push is atomic: either all of the tags are pushed, or none
+    /// are. Other local tags are not pushed.
+    pub fn push_git_tags(
+        &self,
+        remote: &str,
+        tags: &[&str],
+    ) -> Result<(), PushTagsError> {
+        let mut cmd =
+            self.get_git_command(["push", "--atomic", "--porcelain", remote]);
+        cmd.args(tags.iter().map(|tag| format!("refs/tags/{tag}")));
+        run_push(cmd)
+            .map_err(PushTagsError::Process)?
+            .map_err(PushTagsError::Rejected)
+    }
+}
+
+/// Run a `git push --porcelain` command.
+///
+/// The outer result is an error if git fails for a reason other than
+/// rejected refs. The inner result is an error containing the rejected
+/// refs, which is never empty.
+fn run_push(
+    mut cmd: Command,
+) -> Result<Result<(), Vec<RejectedRef>>, RunCommandError> {
+    // Let git's progress and error messages go to the log.
+    cmd.stderr(Stdio::inherit());
+
+    let cmd_str = format_cmd(&cmd);
+    let output = get_cmd_output(cmd)?;
+    if output.status.success() {
+        return Ok(Ok(()));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let rejected = parse_push_rejections(&stdout);
+    if rejected.is_empty() {
+        Err(RunCommandError::NonZeroExit {
+            cmd: cmd_str,
+            status: output.status,
+        })
+    } else {
+        Ok(Err(rejected))
+    }
+}
+
+/// Parse the output of `git ls-remote --tags` into a map from tag name
+/// to commit SHA.
+///
+/// Each line has the form `<sha>\t<ref>`. Annotated tags have an extra
+/// line for the peeled ref `<ref>^{}` with the SHA of the commit, which
+/// takes precedence over the SHA of the tag object.
+fn parse_ls_remote_tags(output: &str) -> BTreeMap<String, String> {
+    let mut tags = BTreeMap::new();
+    for line in output.lines() {
+        let Some((sha, git_ref)) = line.split_once('\t') else {
+            continue;
+        };
+        let Some(tag) = git_ref.strip_prefix("refs/tags/") else {
+            continue;
+        };
+        if let Some(tag) = tag.strip_suffix("^{}") {
+            tags.insert(tag.to_string(), sha.to_string());
+        } else {
+            tags.entry(tag.to_string())
+                .or_insert_with(|| sha.to_string());
+        }
+    }
+    tags
+}
+
+/// Get the rejected refs from the output of `git push --porcelain`.
+///
+/// Each ref is reported on a line of the form
+/// `<flag>\t<from>:<to>\t<summary>`, where the flag is `!` if the ref
+/// was rejected.
+fn parse_push_rejections(output: &str) -> Vec<RejectedRef> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            if parts.next()? != "!" {
+                return None;
+            }
+            let refspec = parts.next()?;
+            let git_ref = refspec.split_once(':').map_or(refspec, |(_, to)| to);
+            Some(RejectedRef {
+                git_ref: git_ref.to_string(),
+                reason: parts.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse a commit from the `git log` format used by [`Repo::commits`].
+fn parse_commit_record(record: &str) -> Option<Commit> {
+    // Records after the first start with the newline that separates
+    // log entries.
+    let record = record.strip_prefix('\n').unwrap_or(record);
+    if record.is_empty() {
+        return None;
+    }
+
+    let mut fields = record.split('\x1f');
+    let mut next = || fields.next().unwrap_or_default();
+    let sha = next().to_string();
+    let parents = next().split_whitespace().map(str::to_string).collect();
+    let author = Identity::new(next(), next());
+    let committer = Identity::new(next(), next());
+    let date = next().to_string();
+    let subject = next().to_string();
+    let body = next().trim_end().to_string();
+    let trailers = parse_trailer_lines(next());
+
+    Some(Commit {
+        sha,
+        parents,
+        author,
+        committer,
+        date,
+        subject,
+        body,
+        trailers,
+    })
+}
+
+/// Parse unfolded `Key: value` trailer lines.
+fn parse_trailer_lines(s: &str) -> Vec<Trailer> {
+    s.lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            Some(Trailer {
+                key: key.trim().to_string(),
+                value: value.trim().to_string(),
+            })
+        })
+        .collect()
 }
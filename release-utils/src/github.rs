@@ -8,7 +8,10 @@
 
 //! Tools for working with the Github API.
 
-use crate::cmd::{RunCommandError, run_cmd};
+use crate::Identity;
+use crate::cmd::{RunCommandError, get_cmd_stdout_utf8, run_cmd};
+use crate::glob::glob_match;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::process::Command;
 
@@ -64,6 +67,125 @@ impl Gh {
         run_cmd(cmd)
     }
 
+    /// Delete the release for the given `tag`. The tag itself is not
+    /// deleted.
+    pub fn delete_release(&self, tag: &str) -> Result<(), RunCommandError> {
+        let mut cmd = Command::new(&self.exe);
+        cmd.args(["release", "delete", tag, "--yes"]);
+        run_cmd(cmd)
+    }
+
+    /// Create a tag in `repository` (e.g. `owner/repo`) with the Github
+    /// API, without needing push access to the remote.
+    ///
+    /// If `opt.message` is set an annotated tag object is created
+    /// first, otherwise the tag is lightweight. Tags created this way
+    /// by a Github App are shown as verified. The tag is not created in
+    /// the local repo.
+    ///
+    /// `repository` may be the `{owner}/{repo}` placeholder, which `gh`
+    /// resolves from the current directory's git remotes.
+    pub fn create_tag(
+        &self,
+        repository: &str,
+        opt: CreateTag,
+    ) -> Result<(), RunCommandError> {
+        let sha = match &opt.message {
+            Some(message) => {
+                let mut cmd = Command::new(&self.exe);
+                cmd.args(["api", &format!("repos/{repository}/git/tags")]);
+                cmd.args(["-f", &format!("tag={}", opt.tag)]);
+                cmd.args(["-f", &format!("message={message}")]);
+                cmd.args(["-f", &format!("object={}", opt.sha)]);
+                cmd.args(["-f", "type=commit"]);
+                if let Some(tagger) = &opt.tagger {
+                    cmd.args(["-f", &format!("tagger[name]={}", tagger.name)]);
+                    cmd.args([
+                        "-f",
+                        &format!("tagger[email]={}", tagger.email),
+                    ]);
+                }
+                cmd.args(["--jq", ".sha"]);
+                get_cmd_stdout_utf8(cmd)?.trim().to_string()
+            }
+            None => opt.sha,
+        };
+
+        let mut cmd = Command::new(&self.exe);
+        cmd.args(["api", &format!("repos/{repository}/git/refs")]);
+        cmd.args(["-f", &format!("ref=refs/tags/{}", opt.tag)]);
+        cmd.args(["-f", &format!("sha={sha}")]);
+        // Capture the response rather than printing it.
+        get_cmd_stdout_utf8(cmd)?;
+        Ok(())
+    }
+
+    /// Get the tags in `repository` matching any of `patterns` (e.g.
+    /// `foo-v*`) with the Github API, mapped to the commit each one
+    /// points at. This is the API equivalent of
+    /// [`Repo::remote_tags`](crate::Repo::remote_tags).
+    ///
+    /// Annotated tags are resolved to their commit, which takes an
+    /// extra API call per annotated tag.
+    pub fn get_tag_commits(
+        &self,
+        repository: &str,
+        patterns: &[&str],
+    ) -> Result<BTreeMap<String, String>, RunCommandError> {
+        let mut tags = BTreeMap::new();
+        for pattern in patterns {
+            // The API matches ref prefixes, so narrow down by the part
+            // of the pattern before the first wildcard.
+            let prefix = pattern.split(['*', '?']).next().unwrap_or("");
+            let mut cmd = Command::new(&self.exe);
+            cmd.args([
+                "api",
+                "--paginate",
+                &format!("repos/{repository}/git/matching-refs/tags/{prefix}"),
+                "--jq",
+                r#".[] | "\(.ref) \(.object.type) \(.object.sha)""#,
+            ]);
+            let output = get_cmd_stdout_utf8(cmd)?;
+            for line in output.lines() {
+                let mut parts = line.split(' ');
+                let (Some(name), Some(kind), Some(sha)) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    continue;
+                };
+                let Some(tag) = name.strip_prefix("refs/tags/") else {
+                    continue;
+                };
+                if !glob_match(pattern, tag) || tags.contains_key(tag) {
+                    continue;
+                }
+                let sha = if kind == "tag" {
+                    self.get_tag_object_commit(repository, sha)?
+                } else {
+                    sha.to_string()
+                };
+                tags.insert(tag.to_string(), sha);
+            }
+        }
+        Ok(tags)
+    }
+
+    /// Get the commit an annotated tag object points at.
+    fn get_tag_object_commit(
+        &self,
+        repository: &str,
+        tag_sha: &str,
+    ) -> Result<String, RunCommandError> {
+        let mut cmd = Command::new(&self.exe);
+        cmd.args([
+            "api",
+            &format!("repos/{repository}/git/tags/{tag_sha}"),
+            "--jq",
+            ".object.sha",
+        ]);
+        Ok(get_cmd_stdout_utf8(cmd)?.trim().to_string())
+    }
+
     /// Check if a release for the given `tag` exists.
     pub fn does_release_exist(
         &self,
@@ -111,3 +233,21 @@ pub struct CreateRelease {
     /// Files to upload and attach to the release.
     pub files: Vec<PathBuf>,
 }
+
+/// Inputs for creating a tag with [`Gh::create_tag`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct CreateTag {
+    /// Name of the tag, without the `refs/tags/` prefix.
+    pub tag: String,
+
+    /// SHA of the commit to tag.
+    pub sha: String,
+
+    /// Message for an annotated tag. If `None`, a lightweight tag is
+    /// created.
+    pub message: Option<String>,
+
+    /// Identity of the tagger of an annotated tag. If `None`, Github
+    /// uses the authenticated user or app.
+    pub tagger: Option<Identity>,
+}
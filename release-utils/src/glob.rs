@@ -0,0 +1,66 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/// Check if `text` matches a simple glob `pattern`.
+///
+/// `*` matches any sequence of characters (including `/`), and `?`
+/// matches any single character. All other characters match
+/// themselves.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let mut p = 0;
+    let mut t = 0;
+    // Position of the last `*` in the pattern, and the position in the
+    // text it is currently matched up to.
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Backtrack: let the last `*` match one more character.
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, t));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("main", "main"));
+        assert!(!glob_match("main", "main2"));
+        assert!(!glob_match("main", "mai"));
+
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "release/1.x"));
+        assert!(glob_match("release/*", "release/1.x"));
+        assert!(!glob_match("release/*", "releases/1.x"));
+        assert!(glob_match("v*.*.*", "v1.2.3"));
+        assert!(!glob_match("v*.*.*", "v1.2"));
+        assert!(glob_match("*-v*", "foo-bar-v1.0.0"));
+
+        assert!(glob_match("v?", "v1"));
+        assert!(!glob_match("v?", "v"));
+        assert!(!glob_match("v?", "v12"));
+    }
+}
@@ -0,0 +1,571 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::date::days_from_civil;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fs, io};
+
+/// Error returned by [`HttpConfig::from_cargo_config`].
+#[derive(Debug)]
+pub enum LoadHttpConfigError {
+    /// Failed to get current directory.
+    CurrentDir(io::Error),
+
+    /// Failed to read a Cargo config file.
+    Read {
+        /// Path of the config file.
+        path: PathBuf,
+        /// Underlying error.
+        err: io::Error,
+    },
+
+    /// A config value has the wrong type.
+    InvalidValue {
+        /// Where the value came from, either a config file path or an
+        /// environment variable name.
+        source: String,
+        /// Name of the config key.
+        key: String,
+    },
+}
+
+impl Display for LoadHttpConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to load cargo http config: ")?;
+        match self {
+            Self::CurrentDir(err) => {
+                write!(f, "failed to get current dir: {err}")
+            }
+            Self::Read { path, err } => {
+                write!(f, "failed to read {}: {err}", path.display())
+            }
+            Self::InvalidValue { source, key } => {
+                write!(f, "invalid value for http.{key} in {source}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadHttpConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::CurrentDir(err) => Some(err),
+            Self::Read { err, .. } => Some(err),
+            Self::InvalidValue { .. } => None,
+        }
+    }
+}
+
+/// Network settings used for HTTP requests.
+///
+/// Most of these mirror the `[http]` table of Cargo's [configuration],
+/// so that requests made by this crate behave the same way as `cargo
+/// publish` on machines that need a proxy or a custom CA bundle.
+///
+/// [configuration]: https://doc.rust-lang.org/cargo/reference/config.html#http
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct HttpConfig {
+    /// Proxy to use for HTTP requests, in libcurl format
+    /// (e.g. `host:port`).
+    pub proxy: Option<String>,
+
+    /// Path to a Certificate Authority bundle file, used to verify TLS
+    /// certificates.
+    pub cainfo: Option<PathBuf>,
+
+    /// Timeout in seconds. As in Cargo, this is used both as the
+    /// connection timeout and as the time a transfer may be below the
+    /// low-speed limit before it is aborted.
+    pub timeout: Option<u64>,
+
+    /// Value of the `User-Agent` header. Defaults to
+    /// [`HttpConfig::DEFAULT_USER_AGENT`].
+    pub user_agent: String,
+
+    /// Maximum total time to spend waiting before retrying requests
+    /// that were rejected with HTTP 429 (Too Many Requests).
+    ///
+    /// The wait time for each retry is taken from the response's
+    /// `Retry-After` header. If waiting would exceed this budget, the
+    /// request fails instead. Set to zero to disable retries.
+    pub retry_budget: Duration,
+}
+
+impl HttpConfig {
+    /// Default `User-Agent` header, identifying this crate and its
+    /// version.
+    pub const DEFAULT_USER_AGENT: &'static str =
+        concat!("release-utils/", env!("CARGO_PKG_VERSION"));
+
+    /// Default value of [`HttpConfig::retry_budget`].
+    pub const DEFAULT_RETRY_BUDGET: Duration = Duration::from_secs(60);
+
+    /// Load settings from Cargo's configuration.
+    ///
+    /// This reads `http.proxy`, `http.cainfo`, `http.timeout`, and
+    /// `http.user-agent` from the `.cargo/config.toml` files in the
+    /// current directory and its parents, then from
+    /// `$CARGO_HOME/config.toml`. Files closer to the current directory
+    /// take precedence. The corresponding `CARGO_HTTP_*` environment
+    /// variables take precedence over all config files.
+    ///
+    /// Only the subset of TOML needed to read these keys is supported;
+    /// anything else in the config files is ignored.
+    pub fn from_cargo_config() -> Result<Self, LoadHttpConfigError> {
+        let cwd =
+            env::current_dir().map_err(LoadHttpConfigError::CurrentDir)?;
+        let cargo_home =
+            env::var_os("CARGO_HOME").map(PathBuf::from).or_else(|| {
+                env::var_os("HOME").map(|home| Path::new(&home).join(".cargo"))
+            });
+        Self::load(&cwd, cargo_home.as_deref(), &|name| env::var(name).ok())
+    }
+
+    fn load(
+        cwd: &Path,
+        cargo_home: Option<&Path>,
+        get_env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Self, LoadHttpConfigError> {
+        // Environment variables have the highest precedence.
+        let mut proxy = get_env("CARGO_HTTP_PROXY");
+        let mut cainfo = get_env("CARGO_HTTP_CAINFO").map(|p| cwd.join(p));
+        let mut user_agent = get_env("CARGO_HTTP_USER_AGENT");
+        let mut timeout = None;
+        if let Some(val) = get_env("CARGO_HTTP_TIMEOUT") {
+            timeout = Some(val.parse().map_err(|_| {
+                LoadHttpConfigError::InvalidValue {
+                    source: "CARGO_HTTP_TIMEOUT".to_string(),
+                    key: "timeout".to_string(),
+                }
+            })?);
+        }
+
+        // Then config files, from the most specific directory to the
+        // least specific.
+        let mut config_files: Vec<PathBuf> = cwd
+            .ancestors()
+            .filter_map(|dir| find_config_file(&dir.join(".cargo")))
+            .collect();
+        if let Some(cargo_home) = cargo_home {
+            if let Some(path) = find_config_file(cargo_home) {
+                if !config_files.contains(&path) {
+                    config_files.push(path);
+                }
+            }
+        }
+
+        // Fill in any unset values from the config files.
+        for path in config_files {
+            let contents = fs::read_to_string(&path).map_err(|err| {
+                LoadHttpConfigError::Read {
+                    path: path.clone(),
+                    err,
+                }
+            })?;
+            let invalid_value = |key: &str| LoadHttpConfigError::InvalidValue {
+                source: path.display().to_string(),
+                key: key.to_string(),
+            };
+
+            for (key, value) in parse_http_table(&contents) {
+                match (key.as_str(), value) {
+                    ("proxy", TomlValue::String(val)) => {
+                        proxy.get_or_insert(val);
+                    }
+                    ("cainfo", TomlValue::String(val)) => {
+                        // Paths in config files are relative to the
+                        // parent of the `.cargo` directory.
+                        let base = path
+                            .parent()
+                            .and_then(Path::parent)
+                            .unwrap_or(&path);
+                        cainfo.get_or_insert(base.join(val));
+                    }
+                    ("timeout", TomlValue::Integer(val)) => {
+                        let val =
+                            val.try_into().map_err(|_| invalid_value(&key))?;
+                        timeout.get_or_insert(val);
+                    }
+                    ("user-agent", TomlValue::String(val)) => {
+                        user_agent.get_or_insert(val);
+                    }
+                    ("proxy" | "cainfo" | "timeout" | "user-agent", _) => {
+                        return Err(invalid_value(&key));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(Self {
+            proxy,
+            cainfo,
+            timeout,
+            user_agent: user_agent
+                .unwrap_or_else(|| Self::DEFAULT_USER_AGENT.to_string()),
+            retry_budget: Self::DEFAULT_RETRY_BUDGET,
+        })
+    }
+
+    /// Add arguments to a `curl` command to apply these settings.
+    pub(crate) fn apply_to_curl(&self, cmd: &mut Command) {
+        cmd.arg("--user-agent");
+        cmd.arg(&self.user_agent);
+        if let Some(proxy) = &self.proxy {
+            cmd.arg("--proxy");
+            cmd.arg(proxy);
+        }
+        if let Some(cainfo) = &self.cainfo {
+            cmd.arg("--cacert");
+            cmd.arg(cainfo);
+        }
+        if let Some(timeout) = self.timeout {
+            let timeout = timeout.to_string();
+            cmd.args(["--connect-timeout", &timeout]);
+            // Cargo's default low-speed limit is 10 bytes per second.
+            cmd.args(["--speed-limit", "10"]);
+            cmd.args(["--speed-time", &timeout]);
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            cainfo: None,
+            timeout: None,
+            user_agent: Self::DEFAULT_USER_AGENT.to_string(),
+            retry_budget: Self::DEFAULT_RETRY_BUDGET,
+        }
+    }
+}
+
+/// Parse the value of a `Retry-After` header into the duration to wait
+/// from `now`.
+///
+/// The value may be either a number of seconds or an HTTP date. Dates
+/// in the past result in a zero duration.
+pub(crate) fn parse_retry_after(
+    value: &str,
+    now: SystemTime,
+) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    // IMF-fixdate, e.g. "Sun, 06 Nov 1994 08:49:37 GMT".
+    let parts: Vec<_> = value.split_ascii_whitespace().collect();
+    let [_weekday, day, month, year, time, "GMT"] = parts[..] else {
+        return None;
+    };
+    let month = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct",
+        "Nov", "Dec",
+    ]
+    .iter()
+    .position(|m| *m == month)?;
+    let mut time = time.split(':').map(|t| t.parse::<u64>().ok());
+    let (Some(Some(hour)), Some(Some(minute)), Some(Some(second)), None) =
+        (time.next(), time.next(), time.next(), time.next())
+    else {
+        return None;
+    };
+
+    let days = days_from_civil(
+        year.parse().ok()?,
+        month as u32 + 1,
+        day.parse().ok()?,
+    );
+    let target = UNIX_EPOCH
+        + Duration::from_secs(
+            u64::try_from(days).ok()? * 86400
+                + hour * 3600
+                + minute * 60
+                + second,
+        );
+    Some(target.duration_since(now).unwrap_or_default())
+}
+
+/// Get the config file in a `.cargo` directory (or `CARGO_HOME`), if
+/// any.
+///
+/// If both `config` and `config.toml` exist, Cargo uses `config`.
+fn find_config_file(dir: &Path) -> Option<PathBuf> {
+    ["config", "config.toml"]
+        .into_iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+#[derive(Debug, PartialEq)]
+enum TomlValue {
+    String(String),
+    Integer(i64),
+    Other,
+}
+
+/// Get the key/value pairs of the `[http]` table from a TOML document.
+///
+/// Both the `[http]` table form and the `http.key = value` dotted-key
+/// form are supported. Dotted keys are only recognized at the top
+/// level, before any table header.
+fn parse_http_table(contents: &str) -> Vec<(String, TomlValue)> {
+    let mut output = Vec::new();
+    let mut in_root_table = true;
+    let mut in_http_table = false;
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.split(']').next().unwrap_or_default();
+            in_root_table = false;
+            in_http_table = header.trim() == "http";
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().trim_matches('"');
+        let key = if in_http_table {
+            key
+        } else if let Some(key) =
+            key.strip_prefix("http.").filter(|_| in_root_table)
+        {
+            key
+        } else {
+            continue;
+        };
+
+        output.push((key.trim().to_string(), parse_toml_value(value.trim())));
+    }
+
+    output
+}
+
+/// Parse a single-line TOML value. Only strings and integers are
+/// supported.
+fn parse_toml_value(value: &str) -> TomlValue {
+    if let Some(rest) = value.strip_prefix('\'') {
+        // Literal string, no escapes.
+        return match rest.split_once('\'') {
+            Some((s, _)) => TomlValue::String(s.to_string()),
+            None => TomlValue::Other,
+        };
+    }
+
+    if let Some(rest) = value.strip_prefix('"') {
+        // Basic string.
+        let mut output = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return TomlValue::String(output),
+                '\\' => match chars.next() {
+                    Some('n') => output.push('\n'),
+                    Some('t') => output.push('\t'),
+                    Some('r') => output.push('\r'),
+                    Some(c @ ('"' | '\\')) => output.push(c),
+                    Some('u') => {
+                        let hex: String = chars.by_ref().take(4).collect();
+                        match u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                        {
+                            Some(c) => output.push(c),
+                            None => return TomlValue::Other,
+                        }
+                    }
+                    _ => return TomlValue::Other,
+                },
+                c => output.push(c),
+            }
+        }
+        return TomlValue::Other;
+    }
+
+    // Strip a trailing comment.
+    let value = value.split('#').next().unwrap_or_default().trim();
+    match value.replace('_', "").parse() {
+        Ok(i) => TomlValue::Integer(i),
+        Err(_) => TomlValue::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_http_table() {
+        let toml = r#"
+[alias]
+proxy = "not this one"
+
+[http]
+proxy = "proxy.example.com:3128" # comment
+cainfo = 'C:\certs\ca.pem'
+timeout = 1_0
+check-revoke = false
+
+[net]
+retry = 3
+"#;
+        assert_eq!(
+            parse_http_table(toml),
+            [
+                (
+                    "proxy".to_string(),
+                    TomlValue::String("proxy.example.com:3128".to_string())
+                ),
+                (
+                    "cainfo".to_string(),
+                    TomlValue::String(r"C:\certs\ca.pem".to_string())
+                ),
+                ("timeout".to_string(), TomlValue::Integer(10)),
+                ("check-revoke".to_string(), TomlValue::Other),
+            ]
+        );
+
+        assert_eq!(
+            parse_http_table("http.proxy = \"a\\u0062c\"\nhttp.timeout=5"),
+            [
+                ("proxy".to_string(), TomlValue::String("abc".to_string())),
+                ("timeout".to_string(), TomlValue::Integer(5)),
+            ]
+        );
+
+        // Dotted keys in another table belong to that table.
+        assert_eq!(
+            parse_http_table(
+                "[target]\nhttp.proxy = \"x\"\n[http]\ntimeout = 5"
+            ),
+            [("timeout".to_string(), TomlValue::Integer(5))]
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let cargo_home = root.join("cargo-home");
+        let project = root.join("project");
+        let subdir = project.join("subdir");
+        fs::create_dir_all(&cargo_home).unwrap();
+        fs::create_dir_all(project.join(".cargo")).unwrap();
+        fs::create_dir_all(&subdir).unwrap();
+
+        fs::write(
+            cargo_home.join("config.toml"),
+            "[http]\nproxy = \"home-proxy\"\ntimeout = 30\n",
+        )
+        .unwrap();
+        fs::write(
+            project.join(".cargo/config.toml"),
+            "[http]\nproxy = \"project-proxy\"\ncainfo = \"certs/ca.pem\"\n",
+        )
+        .unwrap();
+
+        let no_env = |_: &str| None;
+        assert_eq!(
+            HttpConfig::load(&subdir, Some(&cargo_home), &no_env).unwrap(),
+            HttpConfig {
+                proxy: Some("project-proxy".to_string()),
+                cainfo: Some(project.join("certs/ca.pem")),
+                timeout: Some(30),
+                ..HttpConfig::default()
+            }
+        );
+
+        let env = HashMap::from([
+            ("CARGO_HTTP_PROXY", "env-proxy"),
+            ("CARGO_HTTP_TIMEOUT", "5"),
+            ("CARGO_HTTP_USER_AGENT", "my-agent"),
+        ]);
+        let get_env = |name: &str| env.get(name).map(|v| v.to_string());
+        assert_eq!(
+            HttpConfig::load(&subdir, Some(&cargo_home), &get_env).unwrap(),
+            HttpConfig {
+                proxy: Some("env-proxy".to_string()),
+                cainfo: Some(project.join("certs/ca.pem")),
+                timeout: Some(5),
+                user_agent: "my-agent".to_string(),
+                ..HttpConfig::default()
+            }
+        );
+
+        let env = HashMap::from([("CARGO_HTTP_TIMEOUT", "soon")]);
+        let get_env = |name: &str| env.get(name).map(|v| v.to_string());
+        assert!(matches!(
+            HttpConfig::load(&subdir, None, &get_env).unwrap_err(),
+            LoadHttpConfigError::InvalidValue { .. }
+        ));
+    }
+
+    #[test]
+    fn test_apply_to_curl() {
+        let config = HttpConfig {
+            proxy: Some("proxy:3128".to_string()),
+            cainfo: Some(PathBuf::from("/ca.pem")),
+            timeout: Some(30),
+            user_agent: "agent".to_string(),
+            ..HttpConfig::default()
+        };
+        let mut cmd = Command::new("curl");
+        config.apply_to_curl(&mut cmd);
+        assert_eq!(
+            crate::cmd::format_cmd(&cmd),
+            "curl --user-agent agent --proxy proxy:3128 --cacert /ca.pem --connect-timeout 30 \
+             --speed-limit 10 --speed-time 30"
+        );
+    }
+
+    #[test]
+    fn test_default_user_agent() {
+        assert_eq!(
+            HttpConfig::default().user_agent,
+            format!("release-utils/{}", env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        // 2015-10-21 07:28:00 UTC.
+        let now = UNIX_EPOCH + Duration::from_secs(1445412480);
+
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("", now), None);
+        assert_eq!(parse_retry_after("soon", now), None);
+        assert_eq!(
+            parse_retry_after("Wed, 21 Foo 2015 07:28:30 GMT", now),
+            None
+        );
+    }
+}
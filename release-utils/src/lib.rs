@@ -90,15 +90,30 @@
 #![warn(missing_docs)]
 
 mod crate_registry;
-mod env;
+mod date;
 mod git;
+mod glob;
+mod http;
 mod package;
+mod version;
 
+pub mod changes;
 pub mod cmd;
+pub mod env;
 pub mod github;
+pub mod provenance;
 pub mod release;
+pub mod workflow;
 
 pub use crate_registry::{CrateRegistry, GetCrateVersionsError};
 pub use env::{VarError, get_github_sha};
-pub use git::{Repo, RepoOpenError};
-pub use package::{GetLocalVersionError, Package};
+pub use git::{
+    Commit, History, Identity, InProgressOperation, ParseSigningFormatError,
+    PushBranchError, PushBranchOptions, PushTagsError, RejectedRef, Repo,
+    RepoOpenError, SigningFormat, TagOptions, TagSigning, Trailer,
+};
+pub use http::{HttpConfig, LoadHttpConfigError};
+pub use package::{
+    GetLocalVersionError, InvalidTagFormat, Package, ReleaseTag,
+};
+pub use version::{ParseVersionError, Version};
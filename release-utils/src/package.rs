@@ -9,6 +9,7 @@
 use crate::cmd::{
     RunCommandError, format_cmd, get_cmd_stdout_utf8, wait_for_child,
 };
+use crate::{Repo, Version};
 use std::env;
 use std::fmt::{self, Display, Formatter};
 use std::path::{Path, PathBuf};
@@ -23,6 +24,23 @@ pub struct Package {
 
     /// Name of the package.
     name: String,
+
+    /// Format of the package's git tags, see [`Package::set_tag_format`].
+    tag_format: String,
+}
+
+/// Default format of a package's git tags.
+const DEFAULT_TAG_FORMAT: &str = "{name}-v{version}";
+
+/// A git tag for a release of a package, see
+/// [`Package::get_release_tags`].
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ReleaseTag {
+    /// Version of the release.
+    pub version: Version,
+
+    /// Name of the tag.
+    pub tag: String,
 }
 
 impl Package {
@@ -49,6 +67,7 @@ impl Package {
         Self {
             workspace: workspace.into(),
             name: name.into(),
+            tag_format: DEFAULT_TAG_FORMAT.to_string(),
         }
     }
 
@@ -62,43 +81,136 @@ impl Package {
         &self.workspace
     }
 
+    /// Get the format of the package's git tags.
+    pub fn tag_format(&self) -> &str {
+        &self.tag_format
+    }
+
+    /// Set the format of the package's git tags.
+    ///
+    /// `{name}` is replaced with the package name and `{version}` with
+    /// the version. The default is `{name}-v{version}`, e.g.
+    /// `foo-v1.2.3`. A repo with a single package might use
+    /// `v{version}` instead.
+    ///
+    /// The format must contain `{version}`, otherwise
+    /// [`InvalidTagFormat`] is returned and the format is unchanged.
+    pub fn set_tag_format<S>(
+        &mut self,
+        format: S,
+    ) -> Result<(), InvalidTagFormat>
+    where
+        S: Into<String>,
+    {
+        let format = format.into();
+        if !format.contains("{version}") {
+            return Err(InvalidTagFormat(format));
+        }
+        self.tag_format = format;
+        Ok(())
+    }
+
     /// Format a package version as a git tag.
     pub fn get_git_tag_name(&self, local_version: &str) -> String {
-        format!("{}-v{}", self.name, local_version)
+        self.tag_format
+            .replace("{name}", &self.name)
+            .replace("{version}", local_version)
+    }
+
+    /// Parse the version from one of the package's git tags.
+    ///
+    /// Returns `None` if `tag` isn't in the package's tag format or the
+    /// version isn't a valid semantic version.
+    pub fn parse_git_tag(&self, tag: &str) -> Option<Version> {
+        let (prefix, suffix) = self.tag_format.split_once("{version}")?;
+        let prefix = prefix.replace("{name}", &self.name);
+        let suffix = suffix.replace("{name}", &self.name);
+        tag.strip_prefix(&prefix)?
+            .strip_suffix(&suffix)?
+            .parse()
+            .ok()
+    }
+
+    /// Get all of the package's release tags in `repo`, sorted from
+    /// oldest to newest version.
+    ///
+    /// Tags are sorted by semantic version rather than lexically, so
+    /// e.g. `1.10.0` comes after `1.9.0`.
+    pub fn get_release_tags(
+        &self,
+        repo: &Repo,
+    ) -> Result<Vec<ReleaseTag>, RunCommandError> {
+        let pattern = self.get_git_tag_name("*");
+        let mut tags: Vec<_> = repo
+            .list_tags(&pattern)?
+            .into_iter()
+            .filter_map(|tag| {
+                let version = self.parse_git_tag(&tag)?;
+                Some(ReleaseTag { version, tag })
+            })
+            .collect();
+        tags.sort();
+        Ok(tags)
+    }
+
+    /// Get the package's latest release tag in `repo`.
+    ///
+    /// Prereleases are only considered if `include_prerelease` is true.
+    pub fn get_latest_release(
+        &self,
+        repo: &Repo,
+        include_prerelease: bool,
+    ) -> Result<Option<ReleaseTag>, RunCommandError> {
+        Ok(self
+            .get_release_tags(repo)?
+            .into_iter()
+            .rfind(|tag| include_prerelease || !tag.version.is_prerelease()))
+    }
+
+    /// Get the package's latest release tag in `repo` with a version
+    /// lower than `version`, e.g. to find the previous release when
+    /// generating release notes.
+    ///
+    /// Prereleases are only considered if `include_prerelease` is true.
+    /// Note that prereleases of `version` itself (e.g. `2.0.0-rc.1` for
+    /// `2.0.0`) are lower than `version`.
+    pub fn get_latest_release_before(
+        &self,
+        repo: &Repo,
+        version: &Version,
+        include_prerelease: bool,
+    ) -> Result<Option<ReleaseTag>, RunCommandError> {
+        Ok(self.get_release_tags(repo)?.into_iter().rfind(|tag| {
+            (include_prerelease || !tag.version.is_prerelease())
+                && tag.version < *version
+        }))
     }
 
     /// Use `cargo metadata` to get the local version of a package
     /// in the workspace.
     pub fn get_local_version(&self) -> Result<String, GetLocalVersionError> {
-        // Spawn `cargo metadata`. The output goes to a new pipe, which
-        // will be passed as the input to `jq`.
-        let mut metadata_cmd = self.get_cargo_metadata_cmd();
-        let metadata_cmd_str = format_cmd(&metadata_cmd);
-        println!("Running: {metadata_cmd_str}");
-        let mut metadata_proc =
-            metadata_cmd.stdout(Stdio::piped()).spawn().map_err(|err| {
-                GetLocalVersionError::Process(RunCommandError::Launch {
-                    cmd: metadata_cmd_str.clone(),
-                    err,
-                })
-            })?;
-
-        // OK to unwrap, we know stdout is set.
-        let pipe = metadata_proc.stdout.take().unwrap();
-
-        let mut jq_cmd = Command::new("jq");
-        jq_cmd.arg("--raw-output");
-        jq_cmd.arg(format!(
-            ".packages[] | select(.name == \"{}\") | .version",
-            self.name
-        ));
-        jq_cmd.stdin(pipe);
-
-        let mut output = get_cmd_stdout_utf8(jq_cmd)
-            .map_err(GetLocalVersionError::Process)?;
-
-        wait_for_child(metadata_proc, metadata_cmd_str)
-            .map_err(GetLocalVersionError::Process)?;
+        self.get_metadata_field("version")
+    }
+
+    /// Use `cargo metadata` to get the path of the package's
+    /// `Cargo.toml`.
+    pub fn get_manifest_path(&self) -> Result<PathBuf, GetLocalVersionError> {
+        self.get_metadata_field("manifest_path").map(PathBuf::from)
+    }
+
+    /// Use `cargo metadata` to get a string field of the package.
+    fn get_metadata_field(
+        &self,
+        field: &str,
+    ) -> Result<String, GetLocalVersionError> {
+        let mut output = query_cargo_metadata(
+            &self.workspace,
+            &format!(
+                ".packages[] | select(.name == \"{}\") | .{field}",
+                self.name
+            ),
+        )
+        .map_err(GetLocalVersionError::Process)?;
 
         if output.is_empty() {
             Err(GetLocalVersionError::PackageNotFound(
@@ -110,20 +222,97 @@ impl Package {
             Ok(output)
         }
     }
+}
 
-    fn get_cargo_metadata_cmd(&self) -> Command {
-        let mut cmd = Command::new("cargo");
-        cmd.arg("metadata");
-        cmd.args(["--format-version", "1"]);
-        cmd.arg("--manifest-path");
-        cmd.arg(self.workspace.join("Cargo.toml"));
-        // Ignore deps, we only need local packages.
-        cmd.arg("--no-deps");
-        cmd
-    }
+/// A package in the workspace, as reported by `cargo metadata`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct WorkspacePackage {
+    /// Name of the package.
+    pub(crate) name: String,
+
+    /// Absolute path of the package's `Cargo.toml`.
+    pub(crate) manifest_path: PathBuf,
+
+    /// Names of the package's path dependencies.
+    pub(crate) path_deps: Vec<String>,
 }
 
-/// Error returned by [`Package::get_local_version`].
+/// Use `cargo metadata` to get all packages in `workspace`.
+pub(crate) fn get_workspace_packages(
+    workspace: &Path,
+) -> Result<Vec<WorkspacePackage>, RunCommandError> {
+    let output = query_cargo_metadata(
+        workspace,
+        r#".packages[] | [
+             .name,
+             .manifest_path,
+             ([.dependencies[] | select(.path != null) | .name] | join(" "))
+           ] | join("\t")"#,
+    )?;
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            Some(WorkspacePackage {
+                name: fields.next()?.to_string(),
+                manifest_path: PathBuf::from(fields.next()?),
+                path_deps: fields
+                    .next()?
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect(),
+            })
+        })
+        .collect())
+}
+
+/// Run `cargo metadata` for `workspace`, and get the output of the
+/// `jq` `filter` applied to the metadata.
+fn query_cargo_metadata(
+    workspace: &Path,
+    filter: &str,
+) -> Result<String, RunCommandError> {
+    // Spawn `cargo metadata`. The output goes to a new pipe, which
+    // will be passed as the input to `jq`.
+    let mut metadata_cmd = get_cargo_metadata_cmd(workspace);
+    let metadata_cmd_str = format_cmd(&metadata_cmd);
+    println!("Running: {metadata_cmd_str}");
+    let mut metadata_proc = metadata_cmd
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|err| RunCommandError::Launch {
+            cmd: metadata_cmd_str.clone(),
+            err,
+        })?;
+
+    // OK to unwrap, we know stdout is set.
+    let pipe = metadata_proc.stdout.take().unwrap();
+
+    let mut jq_cmd = Command::new("jq");
+    jq_cmd.arg("--raw-output");
+    jq_cmd.arg(filter);
+    jq_cmd.stdin(pipe);
+
+    let output = get_cmd_stdout_utf8(jq_cmd)?;
+
+    wait_for_child(metadata_proc, metadata_cmd_str)?;
+
+    Ok(output)
+}
+
+fn get_cargo_metadata_cmd(workspace: &Path) -> Command {
+    let mut cmd = Command::new("cargo");
+    cmd.arg("metadata");
+    cmd.args(["--format-version", "1"]);
+    cmd.arg("--manifest-path");
+    cmd.arg(workspace.join("Cargo.toml"));
+    // Ignore deps, we only need local packages.
+    cmd.arg("--no-deps");
+    cmd
+}
+
+/// Error returned by [`Package::get_local_version`] and
+/// [`Package::get_manifest_path`].
 #[derive(Debug)]
 pub enum GetLocalVersionError {
     /// A child process failed.
@@ -147,3 +336,16 @@ impl Display for GetLocalVersionError {
 }
 
 impl std::error::Error for GetLocalVersionError {}
+
+/// Error returned by [`Package::set_tag_format`] if the format doesn't
+/// contain `{version}`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidTagFormat(pub String);
+
+impl Display for InvalidTagFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "tag format \"{}\" does not contain {{version}}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidTagFormat {}
@@ -0,0 +1,226 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Record which releases were made from which commit, using git notes.
+//!
+//! Each release is recorded as a block of `Key: value` lines in the
+//! note on the released commit. A commit can have several records, for
+//! example if multiple packages were released from it.
+
+use crate::cmd::RunCommandError;
+use crate::date::civil_from_days;
+use crate::env::detect_ci_environment;
+use crate::{CrateRegistry, GetCrateVersionsError, Package, Repo};
+use std::fmt::{self, Display, Formatter};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Default notes ref for release records.
+pub const DEFAULT_NOTES_REF: &str = "refs/notes/releases";
+
+/// Registry recorded for crates.io releases.
+const CRATES_IO: &str = "crates.io";
+
+/// Record of a release, stored in a git note on the released commit.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ReleaseRecord {
+    /// Name of the package.
+    pub package: String,
+
+    /// Version that was released.
+    pub version: String,
+
+    /// Registry the package was published to: `crates.io`, or the index
+    /// URL of another registry.
+    pub registry: String,
+
+    /// SHA-256 checksum of the published `.crate` file, as lowercase
+    /// hex, as reported by the registry index. `None` if the index
+    /// doesn't have a checksum for the version.
+    pub crate_sha256: Option<String>,
+
+    /// When the release was made, in RFC 3339 format (UTC).
+    pub timestamp: String,
+
+    /// URL of the CI run that made the release.
+    pub run_url: Option<String>,
+}
+
+impl ReleaseRecord {
+    /// Create a record for `version` of `package`, which has just been
+    /// published to `registry`.
+    ///
+    /// The registry is recorded as `crates.io` for the default
+    /// registry, otherwise as its index URL. The checksum is read from
+    /// the registry index, see [`CrateRegistry::get_crate_checksum`].
+    /// The run URL comes from the detected CI environment.
+    pub fn for_release(
+        package: &Package,
+        version: &str,
+        registry: &CrateRegistry,
+    ) -> Result<Self, GetCrateVersionsError> {
+        let crate_sha256 =
+            registry.get_crate_checksum(package.name(), version)?;
+        let registry =
+            if registry.registry_url == CrateRegistry::DEFAULT_REGISTRY {
+                CRATES_IO.to_string()
+            } else {
+                registry.registry_url.clone()
+            };
+
+        Ok(Self {
+            package: package.name().to_string(),
+            version: version.to_string(),
+            registry,
+            crate_sha256,
+            timestamp: format_timestamp(SystemTime::now()),
+            run_url: detect_ci_environment().run_url(),
+        })
+    }
+
+    /// Parse the records in a note. Blocks that are missing required
+    /// fields are skipped, and unknown keys are ignored.
+    pub fn parse_note(note: &str) -> Vec<Self> {
+        note.split("\n\n").filter_map(Self::parse_block).collect()
+    }
+
+    fn parse_block(block: &str) -> Option<Self> {
+        let mut package = None;
+        let mut version = None;
+        let mut registry = None;
+        let mut crate_sha256 = None;
+        let mut timestamp = None;
+        let mut run_url = None;
+        for line in block.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = Some(value.trim().to_string());
+            match key.trim() {
+                "Package" => package = value,
+                "Version" => version = value,
+                "Registry" => registry = value,
+                "Crate-Sha256" => crate_sha256 = value,
+                "Timestamp" => timestamp = value,
+                "Run-Url" => run_url = value,
+                _ => {}
+            }
+        }
+        Some(Self {
+            package: package?,
+            version: version?,
+            registry: registry?,
+            crate_sha256,
+            timestamp: timestamp?,
+            run_url,
+        })
+    }
+}
+
+impl Display for ReleaseRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Package: {}", self.package)?;
+        writeln!(f, "Version: {}", self.version)?;
+        writeln!(f, "Registry: {}", self.registry)?;
+        if let Some(sha256) = &self.crate_sha256 {
+            writeln!(f, "Crate-Sha256: {sha256}")?;
+        }
+        writeln!(f, "Timestamp: {}", self.timestamp)?;
+        if let Some(run_url) = &self.run_url {
+            writeln!(f, "Run-Url: {run_url}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Add `record` to the note on `commit_sha` in `notes_ref`.
+///
+/// This only writes the local note; use [`Repo::push_notes`] to push
+/// it, after [`Repo::fetch_notes`] to pick up existing notes.
+pub fn write_release_record(
+    repo: &Repo,
+    notes_ref: &str,
+    commit_sha: &str,
+    record: &ReleaseRecord,
+) -> Result<(), RunCommandError> {
+    repo.append_note(notes_ref, commit_sha, &record.to_string())
+}
+
+/// Get the releases recorded for `commit_sha` in `notes_ref`.
+///
+/// Notes are read locally; use [`Repo::fetch_notes`] first to get the
+/// remote's records.
+pub fn get_release_records(
+    repo: &Repo,
+    notes_ref: &str,
+    commit_sha: &str,
+) -> Result<Vec<ReleaseRecord>, RunCommandError> {
+    let note = repo.get_note(notes_ref, commit_sha)?;
+    Ok(note
+        .as_deref()
+        .map(ReleaseRecord::parse_note)
+        .unwrap_or_default())
+}
+
+/// Format `time` as an RFC 3339 timestamp in UTC, e.g.
+/// `2024-01-02T03:04:05Z`.
+fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (year, month, day) = civil_from_days(secs / 86400);
+    let secs_of_day = secs % 86400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000-02-29T00:00:00Z"
+        );
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(1_704_164_645)),
+            "2024-01-02T03:04:05Z"
+        );
+    }
+
+    #[test]
+    fn test_parse_note() {
+        let record = ReleaseRecord {
+            package: "foo".to_string(),
+            version: "1.2.0".to_string(),
+            registry: "crates.io".to_string(),
+            crate_sha256: Some("abc123".to_string()),
+            timestamp: "2024-01-02T03:04:05Z".to_string(),
+            run_url: Some("https://example.com/run/1".to_string()),
+        };
+        let minimal = ReleaseRecord {
+            package: "bar".to_string(),
+            crate_sha256: None,
+            run_url: None,
+            ..record.clone()
+        };
+
+        // `git notes append` separates records with a blank line.
+        let note = format!("{record}\n{minimal}\nUnrelated note\n");
+        assert_eq!(ReleaseRecord::parse_note(&note), [record, minimal]);
+        assert_eq!(ReleaseRecord::parse_note(""), []);
+    }
+}
@@ -9,11 +9,22 @@
 //! Utilities for automatically releasing Rust code.
 
 use crate::cmd::{RunCommandError, run_cmd};
+use crate::env::{
+    CiEnvironment, CiEventKind, detect_ci_environment, repo_slug_from_url,
+};
+use crate::github::{CreateTag, Gh};
+use crate::glob::glob_match;
+use crate::provenance::{ReleaseRecord, write_release_record};
 use crate::{
-    CrateRegistry, GetCrateVersionsError, GetLocalVersionError, Package, Repo,
-    VarError, get_github_sha,
+    CrateRegistry, GetCrateVersionsError, GetLocalVersionError, Identity,
+    InProgressOperation, Package, PushTagsError, Repo, TagOptions, TagSigning,
+    VarError,
 };
+use std::collections::BTreeMap;
 use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
 use std::process::Command;
 
 /// Error returned by [`release_packages`].
@@ -25,6 +36,12 @@ pub enum ReleasePackagesError {
     /// A git error occurred.
     Git(Box<dyn std::error::Error + Send + Sync + 'static>),
 
+    /// Failed to look up the published versions of the packages. This
+    /// is only returned if the lookup couldn't be done at all; errors
+    /// for individual packages are reported as
+    /// [`ReleasePackagesError::Package`].
+    RemoteVersions(GetCrateVersionsError),
+
     /// Failed to release a package.
     Package {
         /// Name of the package.
@@ -32,6 +49,76 @@ pub enum ReleasePackagesError {
         /// Underlying error.
         cause: ReleasePackageError,
     },
+
+    /// The work tree has uncommitted changes.
+    DirtyWorkTree(Vec<String>),
+
+    /// The commit to release is not contained in any remote branch.
+    UnpushedCommit(String),
+
+    /// The release was not confirmed at the interactive prompt.
+    NotConfirmed,
+
+    /// The CI job is running for a ref or event that is not allowed
+    /// to release, see [`RefPolicy`].
+    DisallowedRef(DisallowedRef),
+
+    /// Failed to push the new tags. Any crates.io releases have already
+    /// been published at this point.
+    PushTags(PushTagsError),
+
+    /// The checked-out `HEAD` is not the commit being released, see
+    /// [`PreflightChecks::head_is_commit`].
+    HeadMismatch {
+        /// SHA of `HEAD`.
+        head: String,
+        /// SHA of the commit being released.
+        commit: String,
+    },
+
+    /// The commit being released is not on the remote's default branch,
+    /// see [`PreflightChecks::commit_on_default_branch`].
+    NotOnDefaultBranch {
+        /// SHA of the commit being released.
+        commit: String,
+        /// Name of the default branch, or `None` if the remote doesn't
+        /// have one.
+        branch: Option<String>,
+    },
+
+    /// A merge, rebase, or similar operation is in progress, see
+    /// [`PreflightChecks::no_operation_in_progress`].
+    OperationInProgress(InProgressOperation),
+
+    /// Failed to record the releases in git notes, see
+    /// [`ReleaseOptions::notes_ref`]. The releases have already been
+    /// published and tagged at this point.
+    Provenance(RunCommandError),
+
+    /// Failed to get the checksum of a published package from the
+    /// registry index, for its release record. Nothing was recorded.
+    ProvenanceChecksum {
+        /// Name of the package.
+        package: String,
+        /// Underlying error.
+        err: GetCrateVersionsError,
+    },
+
+    /// Tag signing was requested with [`TagBackend::Github`], which
+    /// can't sign tags.
+    UnsupportedSigning,
+
+    /// [`ReleaseOptions::notes_ref`] was set with
+    /// [`TagBackend::Github`]. Pushing the notes needs git push
+    /// credentials, which that backend is meant to avoid.
+    UnsupportedNotes,
+
+    /// The Github repository for [`TagBackend::Github`] couldn't be
+    /// determined from the URL of this remote.
+    GithubRepository(String),
+
+    /// Failed to look up existing tags with the Github API.
+    GithubTags(RunCommandError),
 }
 
 impl Display for ReleasePackagesError {
@@ -39,9 +126,66 @@ impl Display for ReleasePackagesError {
         match self {
             Self::Env(_) => write!(f, "environment error"),
             Self::Git(_) => write!(f, "git error"),
+            Self::RemoteVersions(_) => {
+                write!(f, "failed to get the published package versions")
+            }
             Self::Package { package, .. } => {
                 write!(f, "failed to release package {package}")
             }
+            Self::DirtyWorkTree(paths) => {
+                write!(f, "work tree has uncommitted changes: ")?;
+                write!(f, "{}", paths.join(", "))
+            }
+            Self::UnpushedCommit(commit) => {
+                write!(f, "commit {commit} has not been pushed to a remote")
+            }
+            Self::NotConfirmed => write!(f, "release was not confirmed"),
+            Self::DisallowedRef(reason) => {
+                write!(f, "releasing is not allowed: {reason}")
+            }
+            Self::PushTags(_) => write!(f, "failed to push git tags"),
+            Self::HeadMismatch { head, commit } => {
+                write!(f, "HEAD is {head} rather than {commit}")
+            }
+            Self::NotOnDefaultBranch {
+                commit,
+                branch: Some(branch),
+            } => {
+                write!(
+                    f,
+                    "commit {commit} is not on the default branch {branch}"
+                )
+            }
+            Self::NotOnDefaultBranch {
+                commit,
+                branch: None,
+            } => {
+                write!(f, "commit {commit} is not on a default branch ")?;
+                write!(f, "(the remote has no default branch)")
+            }
+            Self::OperationInProgress(op) => {
+                write!(f, "a {op} is in progress")
+            }
+            Self::Provenance(_) => {
+                write!(f, "failed to record the releases in git notes")
+            }
+            Self::ProvenanceChecksum { package, .. } => {
+                write!(f, "failed to get the checksum of package {package}")
+            }
+            Self::UnsupportedSigning => {
+                write!(f, "tag signing is not supported with the Github API")
+            }
+            Self::UnsupportedNotes => write!(
+                f,
+                "recording releases in git notes is not supported with the Github API"
+            ),
+            Self::GithubRepository(remote) => write!(
+                f,
+                "failed to get the Github repository from the URL of remote {remote}"
+            ),
+            Self::GithubTags(_) => {
+                write!(f, "failed to get the tags from the Github API")
+            }
         }
     }
 }
@@ -51,55 +195,801 @@ impl std::error::Error for ReleasePackagesError {
         match self {
             Self::Env(err) => Some(err),
             Self::Git(err) => Some(&**err),
+            Self::RemoteVersions(err) => Some(err),
             Self::Package { cause, .. } => Some(cause),
+            Self::DirtyWorkTree(_)
+            | Self::UnpushedCommit(_)
+            | Self::NotConfirmed
+            | Self::DisallowedRef(_)
+            | Self::HeadMismatch { .. }
+            | Self::NotOnDefaultBranch { .. }
+            | Self::OperationInProgress(_)
+            | Self::UnsupportedSigning
+            | Self::UnsupportedNotes
+            | Self::GithubRepository(_) => None,
+            Self::PushTags(err) => Some(err),
+            Self::Provenance(err) | Self::GithubTags(err) => Some(err),
+            Self::ProvenanceChecksum { err, .. } => Some(err),
+        }
+    }
+}
+
+/// Options for [`release_packages_with_options`].
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct ReleaseOptions {
+    /// Release from the local checkout rather than from CI.
+    ///
+    /// The commit to release is the checkout's `HEAD`. Releasing fails
+    /// if the work tree has uncommitted changes or if `HEAD` has not
+    /// been pushed to a remote branch. If stdin is a terminal, the user
+    /// is asked to confirm the release.
+    ///
+    /// This allows maintainers to run the same release flow from their
+    /// own machine if CI is unavailable.
+    pub local: bool,
+
+    /// Which refs the CI job is allowed to release from. This is not
+    /// checked in local mode.
+    pub ref_policy: RefPolicy,
+
+    /// How to create the release tags.
+    pub tag: TagConfig,
+
+    /// Remote to push tags to. If `None`, `origin` is used.
+    pub remote: Option<String>,
+
+    /// Checks of the repo's state to run before publishing anything.
+    pub preflight: PreflightChecks,
+
+    /// Notes ref to record releases in, e.g.
+    /// [`DEFAULT_NOTES_REF`](crate::provenance::DEFAULT_NOTES_REF). If
+    /// set, a [`ReleaseRecord`] is added to the note on the released
+    /// commit for each package published during the run, and the notes
+    /// ref is pushed to the remote.
+    pub notes_ref: Option<String>,
+
+    /// Where to create the release tags.
+    pub tag_backend: TagBackend,
+}
+
+/// How release tags are created and published, see
+/// [`ReleaseOptions::tag_backend`].
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum TagBackend {
+    /// Create the tags in the local repo and push them to the remote
+    /// with `git push`. This requires push credentials in the checkout,
+    /// e.g. `persist-credentials` in `actions/checkout`.
+    #[default]
+    Git,
+
+    /// Create the tags directly on Github with the API, which only
+    /// needs a token for `gh`. Tags created by a Github App are shown
+    /// as verified.
+    ///
+    /// Each tag is created right after its package is published, rather
+    /// than pushing all tags at once. Existing tags are also looked up
+    /// through the API. The repository is taken from the URL of
+    /// [`ReleaseOptions::remote`]. [`TagConfig::signing`] and
+    /// [`ReleaseOptions::notes_ref`] are not supported.
+    Github(Gh),
+}
+
+/// [`TagBackend`] with the Github repository resolved.
+enum ResolvedTagBackend<'a> {
+    /// See [`TagBackend::Git`].
+    Git,
+
+    /// See [`TagBackend::Github`].
+    Github {
+        /// The `gh` tool to use.
+        gh: &'a Gh,
+        /// Repository to create tags in, e.g. `owner/repo`.
+        repository: String,
+    },
+}
+
+impl<'a> ResolvedTagBackend<'a> {
+    /// Resolve `backend`, getting the Github repository from the URL of
+    /// `remote`.
+    fn new(
+        backend: &'a TagBackend,
+        repo: &Repo,
+        remote: &str,
+    ) -> Result<Self, ReleasePackagesError> {
+        match backend {
+            TagBackend::Git => Ok(Self::Git),
+            TagBackend::Github(gh) => {
+                let url = repo
+                    .get_remote_url(remote)
+                    .map_err(|err| ReleasePackagesError::Git(Box::new(err)))?;
+                let repository = repo_slug_from_url(&url).ok_or_else(|| {
+                    ReleasePackagesError::GithubRepository(remote.to_string())
+                })?;
+                Ok(Self::Github { gh, repository })
+            }
+        }
+    }
+
+    /// Get the tags matching any of `patterns`, mapped to the commit
+    /// each one points at.
+    fn get_remote_tags(
+        &self,
+        repo: &Repo,
+        remote: &str,
+        patterns: &[&str],
+    ) -> Result<BTreeMap<String, String>, ReleasePackagesError> {
+        match self {
+            Self::Git => repo
+                .remote_tags(remote, patterns)
+                .map_err(|err| ReleasePackagesError::Git(Box::new(err))),
+            Self::Github { gh, repository } => gh
+                .get_tag_commits(repository, patterns)
+                .map_err(ReleasePackagesError::GithubTags),
         }
     }
 }
 
+/// Checks of the repo's state to run before releasing, see
+/// [`ReleaseOptions::preflight`].
+///
+/// All checks are disabled by default. Each failed check has its own
+/// [`ReleasePackagesError`] variant.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct PreflightChecks {
+    /// The work tree must not have modified or untracked files, which
+    /// `cargo publish` would either include in the package or refuse to
+    /// publish. Ignored files are allowed.
+    pub clean_work_tree: bool,
+
+    /// The checked-out `HEAD` must be the commit being released, so
+    /// that the published code matches the tag.
+    pub head_is_commit: bool,
+
+    /// The commit being released must be on the remote's default
+    /// branch, e.g. to prevent releasing from an unmerged branch.
+    pub commit_on_default_branch: bool,
+
+    /// No merge, rebase, cherry-pick or revert may be in progress.
+    pub no_operation_in_progress: bool,
+}
+
+impl PreflightChecks {
+    /// Enable all checks.
+    pub fn all() -> Self {
+        Self {
+            clean_work_tree: true,
+            head_is_commit: true,
+            commit_on_default_branch: true,
+            no_operation_in_progress: true,
+        }
+    }
+
+    /// Run the enabled checks against `repo`, releasing `commit_sha`.
+    /// The remote's default branch is looked up on `remote`.
+    pub fn run(
+        &self,
+        repo: &Repo,
+        commit_sha: &str,
+        remote: &str,
+    ) -> Result<(), ReleasePackagesError> {
+        let git_err = |err| ReleasePackagesError::Git(Box::new(err));
+
+        if self.no_operation_in_progress {
+            if let Some(op) = repo.get_in_progress_operation() {
+                return Err(ReleasePackagesError::OperationInProgress(op));
+            }
+        }
+
+        if self.clean_work_tree {
+            let changes = repo.get_uncommitted_changes().map_err(git_err)?;
+            if !changes.is_empty() {
+                return Err(ReleasePackagesError::DirtyWorkTree(changes));
+            }
+        }
+
+        if self.head_is_commit {
+            let head = repo.head_sha().map_err(git_err)?;
+            if head != commit_sha {
+                return Err(ReleasePackagesError::HeadMismatch {
+                    head,
+                    commit: commit_sha.to_string(),
+                });
+            }
+        }
+
+        if self.commit_on_default_branch {
+            let branch = repo.get_default_branch(remote).map_err(git_err)?;
+            let on_branch = match &branch {
+                Some(branch) => repo
+                    .is_commit_in_remote_branch(commit_sha, remote, branch)
+                    .map_err(git_err)?,
+                None => false,
+            };
+            if !on_branch {
+                return Err(ReleasePackagesError::NotOnDefaultBranch {
+                    commit: commit_sha.to_string(),
+                    branch,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How to create release tags.
+///
+/// By default, lightweight tags are created.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct TagConfig {
+    /// Create annotated tags.
+    pub annotated: bool,
+
+    /// Template for the message of annotated tags. `{package}`,
+    /// `{version}` and `{changelog}` are replaced with the package
+    /// name, the version, and the changelog excerpt for the version.
+    ///
+    /// If `None`, the message is the package name and version, followed
+    /// by the changelog excerpt if there is one.
+    pub message: Option<String>,
+
+    /// Path of the changelog, relative to the package's directory,
+    /// e.g. `CHANGELOG.md`. The section whose heading contains the
+    /// version is used as the changelog excerpt.
+    pub changelog: Option<PathBuf>,
+
+    /// Sign the tags. Signed tags are always annotated.
+    pub signing: Option<TagSigning>,
+
+    /// Identity of the tagger. If `None`, git's configured committer
+    /// identity is used.
+    pub tagger: Option<Identity>,
+}
+
+impl TagConfig {
+    /// Get the options for tagging a release of `package`.
+    fn get_tag_options(
+        &self,
+        package: &Package,
+        version: &str,
+    ) -> Result<TagOptions, ReleasePackageError> {
+        let message = if self.annotated || self.signing.is_some() {
+            let changelog = match &self.changelog {
+                Some(path) => {
+                    let manifest = package
+                        .get_manifest_path()
+                        .map_err(ReleasePackageError::LocalVersion)?;
+                    // OK to unwrap, the manifest path is a file path.
+                    let path = manifest.parent().unwrap().join(path);
+                    let contents =
+                        fs::read_to_string(&path).map_err(|err| {
+                            ReleasePackageError::Changelog { path, err }
+                        })?;
+                    get_changelog_excerpt(&contents, version)
+                }
+                None => None,
+            };
+
+            Some(match &self.message {
+                Some(template) => template
+                    .replace("{package}", package.name())
+                    .replace("{version}", version)
+                    .replace("{changelog}", changelog.as_deref().unwrap_or("")),
+                None => {
+                    let mut message = format!("{} {version}", package.name());
+                    if let Some(changelog) = changelog {
+                        message.push_str("\n\n");
+                        message.push_str(&changelog);
+                    }
+                    message
+                }
+            })
+        } else {
+            None
+        };
+
+        Ok(TagOptions {
+            message,
+            signing: self.signing.clone(),
+            tagger: self.tagger.clone(),
+        })
+    }
+}
+
+/// Get the section of a markdown changelog for `version`.
+///
+/// The section starts after the first heading that contains `version`,
+/// and ends at the next heading of the same or a higher level. Returns
+/// `None` if there is no such heading or the section is empty.
+fn get_changelog_excerpt(changelog: &str, version: &str) -> Option<String> {
+    let heading_level = |line: &str| {
+        let level = line.chars().take_while(|c| *c == '#').count();
+        (level > 0).then_some(level)
+    };
+    let contains_version = |line: &str| {
+        // Check that the version isn't part of a longer version, e.g.
+        // "1.0.0" in "1.0.0-alpha" or "11.0.0".
+        line.match_indices(version).any(|(i, _)| {
+            let before = line[..i].chars().next_back();
+            let after = line[i + version.len()..].chars().next();
+            let is_version_char =
+                |c: char| c.is_ascii_alphanumeric() || c == '.' || c == '-';
+            !before.is_some_and(|c| c.is_ascii_digit() || c == '.')
+                && !after.is_some_and(is_version_char)
+        })
+    };
+
+    let mut lines = changelog.lines();
+    let level = lines.by_ref().find_map(|line| {
+        heading_level(line).filter(|_| contains_version(line))
+    })?;
+
+    let section: Vec<_> = lines
+        .take_while(|line| heading_level(line).is_none_or(|l| l > level))
+        .collect();
+    let section = section.join("\n");
+    let section = section.trim();
+    if section.is_empty() {
+        None
+    } else {
+        Some(section.to_string())
+    }
+}
+
+/// Policy for which refs a CI job may release from.
+///
+/// Pull requests and merge queue entries are never allowed to
+/// release. By default, any branch or tag is allowed.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct RefPolicy {
+    /// Glob patterns of branch names that may release, e.g. `main` or
+    /// `release/*`. If `None`, any branch may release.
+    pub branches: Option<Vec<String>>,
+
+    /// Glob patterns of tag names that may release, e.g. `v*`. If
+    /// `None`, any tag may release.
+    pub tags: Option<Vec<String>>,
+}
+
+impl RefPolicy {
+    /// Check whether the job described by `ci` may release.
+    pub fn check(&self, ci: &dyn CiEnvironment) -> Result<(), DisallowedRef> {
+        match ci.event_kind() {
+            Some(CiEventKind::PullRequest) => {
+                return Err(DisallowedRef::PullRequest {
+                    from_fork: ci.is_fork_pull_request(),
+                });
+            }
+            Some(CiEventKind::MergeQueue) => {
+                return Err(DisallowedRef::MergeQueue);
+            }
+            _ => {}
+        }
+
+        let Some(git_ref) = ci.git_ref() else {
+            if self.branches.is_some() || self.tags.is_some() {
+                return Err(DisallowedRef::UnknownRef);
+            }
+            return Ok(());
+        };
+
+        let matches = |patterns: &Option<Vec<String>>, name: &str| {
+            patterns.as_ref().is_none_or(|patterns| {
+                patterns.iter().any(|pattern| glob_match(pattern, name))
+            })
+        };
+
+        if let Some(branch) = git_ref.strip_prefix("refs/heads/") {
+            // GitHub's merge queue runs on temporary branches, which may
+            // be reported as a push.
+            if branch.starts_with("gh-readonly-queue/") {
+                Err(DisallowedRef::MergeQueue)
+            } else if matches(&self.branches, branch) {
+                Ok(())
+            } else {
+                Err(DisallowedRef::Branch(branch.to_string()))
+            }
+        } else if let Some(tag) = git_ref.strip_prefix("refs/tags/") {
+            if matches(&self.tags, tag) {
+                Ok(())
+            } else {
+                Err(DisallowedRef::Tag(tag.to_string()))
+            }
+        } else if git_ref.starts_with("refs/pull/")
+            || git_ref.starts_with("refs/merge-requests/")
+        {
+            Err(DisallowedRef::PullRequest {
+                from_fork: ci.is_fork_pull_request(),
+            })
+        } else {
+            Err(DisallowedRef::Other(git_ref))
+        }
+    }
+}
+
+/// Reason a CI job is not allowed to release, see [`RefPolicy`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum DisallowedRef {
+    /// The job is running for a pull request.
+    PullRequest {
+        /// Whether the pull request is from a fork.
+        from_fork: bool,
+    },
+
+    /// The job is running for a merge queue entry.
+    MergeQueue,
+
+    /// The branch does not match the allowed patterns.
+    Branch(String),
+
+    /// The tag does not match the allowed patterns.
+    Tag(String),
+
+    /// The ref is neither a branch nor a tag.
+    Other(String),
+
+    /// The ref could not be determined, and the policy restricts
+    /// branches or tags.
+    UnknownRef,
+}
+
+impl Display for DisallowedRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PullRequest { from_fork: true } => {
+                write!(f, "running for a pull request from a fork")
+            }
+            Self::PullRequest { from_fork: false } => {
+                write!(f, "running for a pull request")
+            }
+            Self::MergeQueue => write!(f, "running for a merge queue entry"),
+            Self::Branch(branch) => {
+                write!(f, "branch {branch} is not an allowed release branch")
+            }
+            Self::Tag(tag) => {
+                write!(f, "tag {tag} is not an allowed release tag")
+            }
+            Self::Other(git_ref) => {
+                write!(f, "ref {git_ref} is not a branch or tag")
+            }
+            Self::UnknownRef => {
+                write!(f, "the ref being built could not be determined")
+            }
+        }
+    }
+}
+
+/// Get the commit to release.
+///
+/// In local mode this is the repo's `HEAD`, after checking that the
+/// work tree is clean and that `HEAD` has been pushed. Otherwise the
+/// commit is read from the CI environment.
+pub fn get_release_commit_sha(
+    repo: &Repo,
+    local: bool,
+) -> Result<String, ReleasePackagesError> {
+    let git_err = |err| ReleasePackagesError::Git(Box::new(err));
+
+    if !local {
+        let ci = detect_ci_environment();
+        println!("running in {}", ci.name());
+        return ci.commit_sha().map_err(ReleasePackagesError::Env);
+    }
+
+    println!("running in local mode");
+    let changes = repo.get_uncommitted_changes().map_err(git_err)?;
+    if !changes.is_empty() {
+        return Err(ReleasePackagesError::DirtyWorkTree(changes));
+    }
+
+    let commit_sha = repo.head_sha().map_err(git_err)?;
+    if !repo.is_commit_pushed(&commit_sha).map_err(git_err)? {
+        return Err(ReleasePackagesError::UnpushedCommit(commit_sha));
+    }
+
+    Ok(commit_sha)
+}
+
+/// Ask the user to confirm a local release, if stdin is a terminal.
+fn confirm_local_release(
+    packages: &[Package],
+    commit_sha: &str,
+) -> Result<(), ReleasePackagesError> {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        return Ok(());
+    }
+
+    let names: Vec<_> = packages.iter().map(Package::name).collect();
+    print!("release {} from {commit_sha}? [y/N] ", names.join(", "));
+    // Ignore errors, worst case the prompt isn't shown before reading.
+    let _ = io::stdout().flush();
+
+    let mut answer = String::new();
+    // A read error is treated the same as declining.
+    let _ = stdin.lock().read_line(&mut answer);
+    let answer = answer.trim().to_ascii_lowercase();
+    if answer == "y" || answer == "yes" {
+        Ok(())
+    } else {
+        Err(ReleasePackagesError::NotConfirmed)
+    }
+}
+
 /// Release each package in `packages`, if needed.
 ///
 /// For each package, this will create a remote git tag (if it doesn't
 /// already exist) and a crates.io release (if it doesn't already
 /// exist).
 ///
+/// The new tags are pushed together after all packages have been
+/// published. If a package fails, the tags of the packages released
+/// before it are still pushed.
+///
+/// The commit to release is read from the CI environment, see
+/// [`detect_ci_environment`].
+///
 /// Note that when releasing to crates.io, the order of `packages` may
 /// be significant if the packages depend on one another.
 pub fn release_packages(
     packages: &[Package],
 ) -> Result<(), ReleasePackagesError> {
-    let commit_sha = get_github_sha().map_err(ReleasePackagesError::Env)?;
+    release_packages_with_options(packages, &ReleaseOptions::default())?;
+    Ok(())
+}
 
-    let repo =
+/// Release each package in `packages`, if needed.
+///
+/// This is the same as [`release_packages`], but with additional
+/// options. On success, returns what was done for each package.
+pub fn release_packages_with_options(
+    packages: &[Package],
+    opt: &ReleaseOptions,
+) -> Result<Vec<PackageRelease>, ReleasePackagesError> {
+    if matches!(opt.tag_backend, TagBackend::Github(_)) {
+        if opt.tag.signing.is_some() {
+            return Err(ReleasePackagesError::UnsupportedSigning);
+        }
+        if opt.notes_ref.is_some() {
+            return Err(ReleasePackagesError::UnsupportedNotes);
+        }
+    }
+
+    if !opt.local {
+        opt.ref_policy
+            .check(&*detect_ci_environment())
+            .map_err(ReleasePackagesError::DisallowedRef)?;
+    }
+
+    let remote = opt.remote.as_deref().unwrap_or("origin");
+    let mut repo =
         Repo::open().map_err(|err| ReleasePackagesError::Git(Box::new(err)))?;
-    repo.fetch_git_tags()
-        .map_err(|err| ReleasePackagesError::Git(Box::new(err)))?;
+    repo.set_history_remote(remote);
+    let commit_sha = get_release_commit_sha(&repo, opt.local)?;
+    if opt.local {
+        confirm_local_release(packages, &commit_sha)?;
+    }
+
+    opt.preflight.run(&repo, &commit_sha, remote)?;
+
+    // Check which tags exist on the remote up front, rather than
+    // fetching all tags.
+    let tag_patterns: Vec<_> = packages
+        .iter()
+        .map(|package| package.get_git_tag_name("*"))
+        .collect();
+    let tag_patterns: Vec<_> =
+        tag_patterns.iter().map(String::as_str).collect();
+    let tag_backend = ResolvedTagBackend::new(&opt.tag_backend, &repo, remote)?;
+    let remote_tags =
+        tag_backend.get_remote_tags(&repo, remote, &tag_patterns)?;
 
+    // Look up the published versions of all packages at once. Errors
+    // are reported when the corresponding package is released.
+    let cargo = CrateRegistry::new();
+    let names: Vec<_> = packages.iter().map(Package::name).collect();
+    let mut remote_versions = cargo
+        .get_many_crate_versions(&names)
+        .map_err(ReleasePackagesError::RemoteVersions)?;
+
+    let mut releases = Vec::new();
     for package in packages {
-        auto_release_package(&repo, package, &commit_sha).map_err(|err| {
-            ReleasePackagesError::Package {
-                package: package.name().to_string(),
-                cause: err,
+        // There is a result for every package, but if a package is
+        // listed twice the result has already been used, so look it up
+        // again.
+        let versions = remote_versions
+            .remove(package.name())
+            .unwrap_or_else(|| cargo.get_crate_versions(package.name()));
+        let result = release_package_impl(
+            &repo,
+            package,
+            &commit_sha,
+            versions,
+            &remote_tags,
+            &opt.tag,
+            &tag_backend,
+        );
+        match result {
+            Ok(release) => releases.push(release),
+            Err(err) => {
+                // The packages released before this one are already
+                // published, so push their tags and record them before
+                // failing. Otherwise a later run from a different
+                // commit would tag the published versions there.
+                if let Err(finish_err) = finish_releases(
+                    &repo,
+                    &cargo,
+                    remote,
+                    &commit_sha,
+                    packages,
+                    &releases,
+                    opt,
+                ) {
+                    println!("warning: {finish_err}");
+                }
+                return Err(ReleasePackagesError::Package {
+                    package: package.name().to_string(),
+                    cause: err,
+                });
             }
-        })?;
+        }
     }
 
+    finish_releases(
+        &repo,
+        &cargo,
+        remote,
+        &commit_sha,
+        packages,
+        &releases,
+        opt,
+    )?;
+    Ok(releases)
+}
+
+/// Push the tags created during the run and record the published
+/// packages. `releases` may be shorter than `packages` if a package
+/// failed to release.
+fn finish_releases(
+    repo: &Repo,
+    registry: &CrateRegistry,
+    remote: &str,
+    commit_sha: &str,
+    packages: &[Package],
+    releases: &[PackageRelease],
+    opt: &ReleaseOptions,
+) -> Result<(), ReleasePackagesError> {
+    // Push all the new tags at once, so that a rejected push doesn't
+    // leave some packages tagged and others not. Tags created with the
+    // Github API already exist on the remote.
+    let new_tags: Vec<_> = releases
+        .iter()
+        .filter(|release| release.tagged)
+        .map(|release| release.tag.as_str())
+        .collect();
+    if opt.tag_backend == TagBackend::Git && !new_tags.is_empty() {
+        repo.push_git_tags(remote, &new_tags)
+            .map_err(ReleasePackagesError::PushTags)?;
+    }
+
+    if let Some(notes_ref) = &opt.notes_ref {
+        record_releases(
+            repo, registry, remote, notes_ref, commit_sha, packages, releases,
+        )?;
+    }
     Ok(())
 }
 
-/// Error returned by [`auto_release_package`].
+/// Record the packages published during the run in the note on
+/// `commit_sha`, and push the notes.
+fn record_releases(
+    repo: &Repo,
+    registry: &CrateRegistry,
+    remote: &str,
+    notes_ref: &str,
+    commit_sha: &str,
+    packages: &[Package],
+    releases: &[PackageRelease],
+) -> Result<(), ReleasePackagesError> {
+    let records = packages
+        .iter()
+        .zip(releases)
+        .filter(|(_, release)| release.published)
+        .map(|(package, release)| {
+            ReleaseRecord::for_release(package, &release.version, registry)
+                .map_err(|err| ReleasePackagesError::ProvenanceChecksum {
+                    package: package.name().to_string(),
+                    err,
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    // Start from the remote's notes so that the push is a fast-forward.
+    (|| {
+        repo.fetch_notes(remote, notes_ref)?;
+        for record in &records {
+            write_release_record(repo, notes_ref, commit_sha, record)?;
+        }
+        repo.push_notes(remote, notes_ref)
+    })()
+    .map_err(ReleasePackagesError::Provenance)
+}
+
+/// Result of releasing a single package.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct PackageRelease {
+    /// Name of the package.
+    pub package: String,
+
+    /// Version of the package that was released.
+    pub version: String,
+
+    /// Git tag for the release.
+    pub tag: String,
+
+    /// Whether the crate was published to crates.io during this run.
+    /// This is false if the version had already been published.
+    pub published: bool,
+
+    /// Whether the git tag was created during this run. This is false
+    /// if the tag already existed.
+    pub tagged: bool,
+}
+
+impl PackageRelease {
+    /// Whether any part of the release was done during this run.
+    pub fn is_new(&self) -> bool {
+        self.published || self.tagged
+    }
+}
+
+/// Error returned by [`auto_release_package`] and [`release_package`].
 #[derive(Debug)]
 pub enum ReleasePackageError {
     /// Failed to get the local version.
     LocalVersion(GetLocalVersionError),
 
     /// Failed to get the published versions of the crate.
+    ///
+    /// The inner error can be used to decide whether retrying may help,
+    /// see [`GetCrateVersionsError::is_transient`].
     RemoteVersions(GetCrateVersionsError),
 
     /// Failed to publish the crate.
     Publish(RunCommandError),
 
-    /// Failed to create or push the git tag.
+    /// Failed to create the git tag.
     Git(RunCommandError),
+
+    /// Failed to push the git tag.
+    PushTag(PushTagsError),
+
+    /// Failed to create the git tag with the Github API, see
+    /// [`TagBackend::Github`].
+    GithubTag(RunCommandError),
+
+    /// Failed to read the changelog for the tag message.
+    Changelog {
+        /// Path of the changelog.
+        path: PathBuf,
+        /// Underlying error.
+        err: io::Error,
+    },
+
+    /// The release's tag already exists but points at a different
+    /// commit. For a tag on the remote this is only an error if the
+    /// version hasn't been published yet.
+    TagMismatch {
+        /// Name of the tag.
+        tag: String,
+        /// Commit the existing tag points at.
+        tag_commit: String,
+        /// Commit being released.
+        commit: String,
+    },
 }
 
 impl Display for ReleasePackageError {
@@ -113,6 +1003,21 @@ impl Display for ReleasePackageError {
             }
             Self::Publish(_) => write!(f, "failed to publish the crate"),
             Self::Git(_) => write!(f, "git error"),
+            Self::PushTag(_) => write!(f, "failed to push the git tag"),
+            Self::GithubTag(_) => {
+                write!(f, "failed to create the git tag with the Github API")
+            }
+            Self::Changelog { path, .. } => {
+                write!(f, "failed to read {}", path.display())
+            }
+            Self::TagMismatch {
+                tag,
+                tag_commit,
+                commit,
+            } => write!(
+                f,
+                "git tag {tag} points at {tag_commit} rather than {commit}"
+            ),
         }
     }
 }
@@ -124,6 +1029,10 @@ impl std::error::Error for ReleasePackageError {
             Self::RemoteVersions(err) => Some(err),
             Self::Publish(err) => Some(err),
             Self::Git(err) => Some(err),
+            Self::PushTag(err) => Some(err),
+            Self::GithubTag(err) => Some(err),
+            Self::Changelog { err, .. } => Some(err),
+            Self::TagMismatch { .. } => None,
         }
     }
 }
@@ -132,41 +1041,169 @@ impl std::error::Error for ReleasePackageError {
 ///
 /// This publishes to crates.io if the corresponding version does not already
 /// exist there, and also pushes a new git tag if one doesn't exist yet.
+///
+/// Tags are checked on and pushed to `origin`, the same default as
+/// [`ReleaseOptions::remote`]. Use [`release_package`] to choose the
+/// remote and registry.
 pub fn auto_release_package(
     repo: &Repo,
     package: &Package,
     commit_sha: &str,
 ) -> Result<(), ReleasePackageError> {
+    release_package(repo, package, commit_sha, &CrateRegistry::new(), "origin")
+        .map(|_| ())
+}
+
+/// Release a single package, if needed, and return what was done.
+///
+/// The published versions are looked up in `registry`, and `cargo
+/// publish` is run if the local version is missing. Which tags exist is
+/// checked on `remote` with [`Repo::remote_tags`], and a new tag is
+/// pushed to `remote`.
+///
+/// If the tag already exists but points at a commit other than
+/// `commit_sha`, [`ReleasePackageError::TagMismatch`] is returned
+/// without publishing. The exception is if the version has already been
+/// published, in which case it is assumed to have been published from
+/// the tagged commit.
+pub fn release_package(
+    repo: &Repo,
+    package: &Package,
+    commit_sha: &str,
+    registry: &CrateRegistry,
+    remote: &str,
+) -> Result<PackageRelease, ReleasePackageError> {
+    let remote_versions = registry.get_crate_versions(package.name());
+    let remote_tags = repo
+        .remote_tags(remote, &[&package.get_git_tag_name("*")])
+        .map_err(ReleasePackageError::Git)?;
+    let release = release_package_impl(
+        repo,
+        package,
+        commit_sha,
+        remote_versions,
+        &remote_tags,
+        &TagConfig::default(),
+        &ResolvedTagBackend::Git,
+    )?;
+    if release.tagged {
+        repo.push_git_tags(remote, &[&release.tag])
+            .map_err(ReleasePackageError::PushTag)?;
+    }
+    Ok(release)
+}
+
+/// Release a single package, given the result of looking up its
+/// published versions.
+///
+/// With [`TagBackend::Git`] the tag is created locally but not pushed.
+/// With [`TagBackend::Github`] it is created on Github directly.
+fn release_package_impl(
+    repo: &Repo,
+    package: &Package,
+    commit_sha: &str,
+    remote_versions: Result<Vec<String>, GetCrateVersionsError>,
+    remote_tags: &BTreeMap<String, String>,
+    tag_config: &TagConfig,
+    tag_backend: &ResolvedTagBackend,
+) -> Result<PackageRelease, ReleasePackageError> {
     let local_version = package
         .get_local_version()
         .map_err(ReleasePackageError::LocalVersion)?;
     println!("local version of {} is {local_version}", package.name());
 
     // Create the crates.io release if it doesn't exist.
-    if does_crates_io_release_exist(package, &local_version)
-        .map_err(ReleasePackageError::RemoteVersions)?
+    let remote_versions = match remote_versions {
+        Ok(v) => v,
+        Err(GetCrateVersionsError::NotPublished) => Vec::new(),
+        Err(err) => return Err(ReleasePackageError::RemoteVersions(err)),
+    };
+    let already_published = remote_versions.contains(&local_version);
+
+    // Check for an existing tag before publishing, so that a tag left
+    // on the wrong commit doesn't result in a release that doesn't
+    // match its tag.
+    // The remote is the source of truth for which tags exist. A tag
+    // that only exists locally (e.g. from an earlier run whose push
+    // failed) is reused if it points at the right commit.
+    let tag = package.get_git_tag_name(&local_version);
+    let (tag_commit, on_remote) = match remote_tags.get(&tag) {
+        Some(sha) => (Some(sha.clone()), true),
+        None => (
+            repo.get_tag_commit(&tag)
+                .map_err(ReleasePackageError::Git)?,
+            false,
+        ),
+    };
+    if let Some(tag_commit) = &tag_commit {
+        if tag_commit != commit_sha && !(on_remote && already_published) {
+            return Err(ReleasePackageError::TagMismatch {
+                tag,
+                tag_commit: tag_commit.clone(),
+                commit: commit_sha.to_string(),
+            });
+        }
+    }
+
+    // Get the tag options before publishing, so that a failure (e.g.
+    // a missing changelog) doesn't leave a published crate without a
+    // tag. `None` means no new tag is needed.
+    let new_tag_opt = if on_remote
+        || (tag_commit.is_some()
+            && matches!(tag_backend, ResolvedTagBackend::Git))
     {
+        None
+    } else {
+        Some(tag_config.get_tag_options(package, &local_version)?)
+    };
+
+    let published = if already_published {
         println!(
             "{}-{local_version} has already been published",
             package.name()
         );
+        false
     } else {
         publish_package(package).map_err(ReleasePackageError::Publish)?;
-    }
+        true
+    };
 
-    // Create the remote git tag if it doesn't exist.
-    let tag = package.get_git_tag_name(&local_version);
-    if repo
-        .does_git_tag_exist(&tag)
-        .map_err(ReleasePackageError::Git)?
-    {
+    // Create the git tag if it doesn't exist on the remote.
+    let tagged = if on_remote {
         println!("git tag {tag} already exists");
+        false
+    } else if let Some(tag_opt) = new_tag_opt {
+        match tag_backend {
+            ResolvedTagBackend::Git => repo
+                .make_git_tag(&tag, commit_sha, &tag_opt)
+                .map_err(ReleasePackageError::Git)?,
+            ResolvedTagBackend::Github { gh, repository } => {
+                println!("creating git tag {tag} with the Github API");
+                gh.create_tag(
+                    repository,
+                    CreateTag {
+                        tag: tag.clone(),
+                        sha: commit_sha.to_string(),
+                        message: tag_opt.message,
+                        tagger: tag_opt.tagger,
+                    },
+                )
+                .map_err(ReleasePackageError::GithubTag)?
+            }
+        }
+        true
     } else {
-        repo.make_and_push_git_tag(&tag, commit_sha)
-            .map_err(ReleasePackageError::Git)?;
-    }
+        println!("using existing local git tag {tag}");
+        true
+    };
 
-    Ok(())
+    Ok(PackageRelease {
+        package: package.name().to_string(),
+        version: local_version,
+        tag,
+        published,
+        tagged,
+    })
 }
 
 /// Check if a new release of `package` should be published.
@@ -188,9 +1225,409 @@ pub fn does_crates_io_release_exist(
     Ok(false)
 }
 
+/// Options for [`rollback_release`].
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct RollbackOptions {
+    /// Actually roll back. If false, [`rollback_release`] only prints
+    /// what it would do and returns [`RollbackError::NotConfirmed`].
+    pub confirm: bool,
+
+    /// Remote to delete tags from. If `None`, `origin` is used.
+    pub remote: Option<String>,
+
+    /// If set, Github releases for the tags are deleted as well.
+    pub github: Option<Gh>,
+
+    /// Delete tags even if their version has already been published.
+    /// By default such tags are skipped with a warning, since the
+    /// published crate can't be removed and a later run would tag it
+    /// at a different commit.
+    pub allow_published: bool,
+}
+
+/// Error returned by [`rollback_release`].
+#[derive(Debug)]
+pub enum RollbackError {
+    /// [`RollbackOptions::confirm`] was not set, nothing was changed.
+    NotConfirmed,
+
+    /// Failed to get the published versions of a package.
+    RemoteVersions {
+        /// Name of the package.
+        package: String,
+        /// Underlying error.
+        err: GetCrateVersionsError,
+    },
+
+    /// A tag points at a different commit than the one the failed run
+    /// released. Nothing was changed.
+    TagMismatch {
+        /// Name of the tag.
+        tag: String,
+        /// Commit the tag points at.
+        commit: String,
+    },
+
+    /// Failed to delete the Github release for a tag.
+    GithubRelease {
+        /// Tag of the release.
+        tag: String,
+        /// Underlying error.
+        err: RunCommandError,
+    },
+
+    /// Failed to delete a tag.
+    Tag {
+        /// Name of the tag.
+        tag: String,
+        /// Underlying error.
+        err: RunCommandError,
+    },
+}
+
+impl Display for RollbackError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotConfirmed => write!(f, "rollback was not confirmed"),
+            Self::RemoteVersions { package, .. } => {
+                write!(f, "failed to get the published versions of {package}")
+            }
+            Self::TagMismatch { tag, commit } => {
+                write!(f, "tag {tag} points at a different commit ({commit})")
+            }
+            Self::GithubRelease { tag, .. } => {
+                write!(f, "failed to delete the Github release for {tag}")
+            }
+            Self::Tag { tag, .. } => write!(f, "failed to delete tag {tag}"),
+        }
+    }
+}
+
+impl std::error::Error for RollbackError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::NotConfirmed | Self::TagMismatch { .. } => None,
+            Self::RemoteVersions { err, .. } => Some(err),
+            Self::GithubRelease { err, .. } | Self::Tag { err, .. } => {
+                Some(err)
+            }
+        }
+    }
+}
+
+/// Undo the tagging and Github release steps of a failed release run,
+/// so that the release can be retried, e.g. from a fixed commit.
+///
+/// For the tag of each of `releases`, the Github release is deleted
+/// (if [`RollbackOptions::github`] is set), then the tag is deleted
+/// from the remote and locally. Missing releases and tags are skipped.
+/// The tags created by a run are those of the [`PackageRelease`]s with
+/// `tagged` set.
+///
+/// Before anything is changed, every tag is checked:
+/// * If the tag points at a commit other than `commit_sha` (the commit
+///   the failed run released), [`RollbackError::TagMismatch`] is
+///   returned.
+/// * If the version has already been published to `registry`, the tag
+///   is skipped with a warning, unless
+///   [`RollbackOptions::allow_published`] is set.
+///
+/// Crates published to the registry are never touched, since
+/// publishing can't be undone; bump the version to release again
+/// instead.
+pub fn rollback_release(
+    repo: &Repo,
+    registry: &CrateRegistry,
+    commit_sha: &str,
+    releases: &[PackageRelease],
+    opt: &RollbackOptions,
+) -> Result<(), RollbackError> {
+    let remote = opt.remote.as_deref().unwrap_or("origin");
+
+    let mut tags = Vec::new();
+    for release in releases {
+        let tag = release.tag.as_str();
+        let tag_err = |err| RollbackError::Tag {
+            tag: tag.to_string(),
+            err,
+        };
+        let remote_commit = repo
+            .remote_tags(remote, &[tag])
+            .map_err(tag_err)?
+            .remove(tag);
+        let local_commit = repo.get_tag_commit(tag).map_err(tag_err)?;
+        let mismatch = remote_commit
+            .into_iter()
+            .chain(local_commit)
+            .find(|commit| commit != commit_sha);
+        if let Some(commit) = mismatch {
+            return Err(RollbackError::TagMismatch {
+                tag: tag.to_string(),
+                commit,
+            });
+        }
+
+        if !opt.allow_published {
+            let published = match registry.get_crate_versions(&release.package)
+            {
+                Ok(versions) => versions.contains(&release.version),
+                Err(GetCrateVersionsError::NotPublished) => false,
+                Err(err) => {
+                    return Err(RollbackError::RemoteVersions {
+                        package: release.package.clone(),
+                        err,
+                    });
+                }
+            };
+            if published {
+                println!(
+                    "warning: not deleting tag {tag}, {} {} has already been published",
+                    release.package, release.version
+                );
+                continue;
+            }
+        }
+        tags.push(tag);
+    }
+
+    if !opt.confirm {
+        for tag in &tags {
+            if opt.github.is_some() {
+                println!("would delete the Github release for {tag}");
+            }
+            println!("would delete tag {tag} locally and from {remote}");
+        }
+        return Err(RollbackError::NotConfirmed);
+    }
+
+    for tag in tags {
+        if let Some(gh) = &opt.github {
+            let exists = gh.does_release_exist(tag).map_err(|err| {
+                RollbackError::GithubRelease {
+                    tag: tag.to_string(),
+                    err,
+                }
+            })?;
+            if exists {
+                gh.delete_release(tag).map_err(|err| {
+                    RollbackError::GithubRelease {
+                        tag: tag.to_string(),
+                        err,
+                    }
+                })?;
+            }
+        }
+
+        repo.delete_tag(tag, Some(remote)).map_err(|err| {
+            RollbackError::Tag {
+                tag: tag.to_string(),
+                err,
+            }
+        })?;
+    }
+    Ok(())
+}
+
 /// Publish `package` to crates.io.
 pub fn publish_package(package: &Package) -> Result<(), RunCommandError> {
     let mut cmd = Command::new("cargo");
     cmd.args(["publish", "--package", package.name()]);
     run_cmd(cmd)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VarError;
+
+    #[derive(Debug, Default)]
+    struct FakeCi {
+        git_ref: Option<&'static str>,
+        event_kind: Option<CiEventKind>,
+        fork: bool,
+    }
+
+    impl CiEnvironment for FakeCi {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn commit_sha(&self) -> Result<String, VarError> {
+            Ok("abc".to_string())
+        }
+
+        fn git_ref(&self) -> Option<String> {
+            self.git_ref.map(|r| r.to_string())
+        }
+
+        fn event_kind(&self) -> Option<CiEventKind> {
+            self.event_kind.clone()
+        }
+
+        fn repository(&self) -> Option<String> {
+            None
+        }
+
+        fn run_url(&self) -> Option<String> {
+            None
+        }
+
+        fn is_fork_pull_request(&self) -> bool {
+            self.fork
+        }
+    }
+
+    fn push(git_ref: &'static str) -> FakeCi {
+        FakeCi {
+            git_ref: Some(git_ref),
+            event_kind: Some(CiEventKind::Push),
+            fork: false,
+        }
+    }
+
+    #[test]
+    fn test_get_changelog_excerpt() {
+        let changelog = "\
+# Changelog
+
+## 1.0.0-alpha
+
+* Prerelease.
+
+## [1.0.0] - 2024-01-01
+
+* Added `foo`.
+
+### Fixed
+
+* Fixed `bar`.
+
+## 0.9.0
+
+* Old.
+";
+        assert_eq!(
+            get_changelog_excerpt(changelog, "1.0.0").unwrap(),
+            "* Added `foo`.\n\n### Fixed\n\n* Fixed `bar`."
+        );
+        assert_eq!(
+            get_changelog_excerpt(changelog, "1.0.0-alpha").unwrap(),
+            "* Prerelease."
+        );
+        assert_eq!(
+            get_changelog_excerpt(changelog, "0.9.0").unwrap(),
+            "* Old."
+        );
+        assert_eq!(get_changelog_excerpt(changelog, "0.0.9"), None);
+        assert_eq!(get_changelog_excerpt("## 2.0.0\n## 1.0.0", "2.0.0"), None);
+    }
+
+    #[test]
+    fn test_ref_policy_default() {
+        let policy = RefPolicy::default();
+        assert_eq!(policy.check(&push("refs/heads/anything")), Ok(()));
+        assert_eq!(policy.check(&push("refs/tags/v1.0.0")), Ok(()));
+        assert_eq!(policy.check(&FakeCi::default()), Ok(()));
+
+        assert_eq!(
+            policy.check(&FakeCi {
+                git_ref: Some("refs/pull/1/merge"),
+                event_kind: Some(CiEventKind::PullRequest),
+                fork: true,
+            }),
+            Err(DisallowedRef::PullRequest { from_fork: true })
+        );
+        assert_eq!(
+            policy.check(&FakeCi {
+                git_ref: Some("refs/pull/1/merge"),
+                ..Default::default()
+            }),
+            Err(DisallowedRef::PullRequest { from_fork: false })
+        );
+        assert_eq!(
+            policy.check(&FakeCi {
+                event_kind: Some(CiEventKind::MergeQueue),
+                ..Default::default()
+            }),
+            Err(DisallowedRef::MergeQueue)
+        );
+        assert_eq!(
+            policy.check(&push("refs/heads/gh-readonly-queue/main/pr-1-abc")),
+            Err(DisallowedRef::MergeQueue)
+        );
+        assert_eq!(
+            policy.check(&push("refs/remotes/origin/main")),
+            Err(DisallowedRef::Other("refs/remotes/origin/main".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_ref_policy_patterns() {
+        let policy = RefPolicy {
+            branches: Some(vec!["main".to_string(), "release/*".to_string()]),
+            tags: Some(vec!["v*".to_string()]),
+        };
+        assert_eq!(policy.check(&push("refs/heads/main")), Ok(()));
+        assert_eq!(policy.check(&push("refs/heads/release/1.x")), Ok(()));
+        assert_eq!(policy.check(&push("refs/tags/v1.0.0")), Ok(()));
+        assert_eq!(
+            policy.check(&push("refs/heads/feature")),
+            Err(DisallowedRef::Branch("feature".to_string()))
+        );
+        assert_eq!(
+            policy.check(&push("refs/tags/foo")),
+            Err(DisallowedRef::Tag("foo".to_string()))
+        );
+        assert_eq!(
+            policy.check(&FakeCi::default()),
+            Err(DisallowedRef::UnknownRef)
+        );
+
+        let policy = RefPolicy {
+            branches: Some(vec!["main".to_string()]),
+            tags: Some(Vec::new()),
+        };
+        assert_eq!(
+            policy.check(&push("refs/tags/v1.0.0")),
+            Err(DisallowedRef::Tag("v1.0.0".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_tag_options_before_publish() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"foo\"\nversion = \"1.0.0\"\n\
+             edition = \"2021\"\npublish = false\n",
+        )
+        .unwrap();
+        fs::create_dir(dir.join("src")).unwrap();
+        fs::write(dir.join("src/lib.rs"), "").unwrap();
+        let mut cmd = Command::new("git");
+        cmd.arg("-C").arg(dir).args(["init", "--quiet"]);
+        run_cmd(cmd).unwrap();
+        let repo = Repo::open_path(dir).unwrap();
+        let package = Package::with_workspace("foo", dir);
+
+        // The changelog is read before publishing, so the error is
+        // reported instead of a publish failure.
+        let tag_config = TagConfig {
+            changelog: Some(PathBuf::from("MISSING.md")),
+            annotated: true,
+            ..Default::default()
+        };
+        let err = release_package_impl(
+            &repo,
+            &package,
+            "abc",
+            Err(GetCrateVersionsError::NotPublished),
+            &BTreeMap::new(),
+            &tag_config,
+            &ResolvedTagBackend::Git,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ReleasePackageError::Changelog { .. }));
+    }
+}
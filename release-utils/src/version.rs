@@ -0,0 +1,265 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// A [semantic version], e.g. `1.2.3-beta.1+build`.
+///
+/// Versions are ordered by semver precedence: prereleases come before
+/// the corresponding release, and prerelease identifiers are compared
+/// numerically where possible. Build metadata does not affect
+/// precedence, but is compared last so that the ordering is consistent
+/// with equality.
+///
+/// [semantic version]: https://semver.org
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Version {
+    /// Major version.
+    pub major: u64,
+
+    /// Minor version.
+    pub minor: u64,
+
+    /// Patch version.
+    pub patch: u64,
+
+    /// Prerelease identifiers, e.g. `beta.1`. Empty for releases.
+    pub pre: String,
+
+    /// Build metadata, e.g. `build.5`. Usually empty.
+    pub build: String,
+}
+
+impl Version {
+    /// Create a release version with no prerelease or build metadata.
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            pre: String::new(),
+            build: String::new(),
+        }
+    }
+
+    /// Whether this is a prerelease version.
+    pub fn is_prerelease(&self) -> bool {
+        !self.pre.is_empty()
+    }
+}
+
+/// Error returned when parsing an invalid [`Version`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseVersionError(pub String);
+
+impl Display for ParseVersionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid semantic version \"{}\"", self.0)
+    }
+}
+
+impl std::error::Error for ParseVersionError {}
+
+impl FromStr for Version {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseVersionError(s.to_string());
+
+        let (rest, build) = match s.split_once('+') {
+            Some((rest, build)) => (rest, Some(build)),
+            None => (s, None),
+        };
+        let (core, pre) = match rest.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (rest, None),
+        };
+
+        let mut nums = core.split('.').map(parse_numeric);
+        let (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) =
+            (nums.next(), nums.next(), nums.next(), nums.next())
+        else {
+            return Err(err());
+        };
+
+        // Identifiers must be non-empty and alphanumeric (plus hyphens).
+        // Numeric prerelease identifiers can't have leading zeros.
+        let is_valid_id = |id: &str| {
+            !id.is_empty()
+                && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        };
+        let is_valid_pre_id = |id: &str| {
+            is_valid_id(id)
+                && (!id.chars().all(|c| c.is_ascii_digit())
+                    || parse_numeric(id).is_some())
+        };
+        if !pre.is_none_or(|pre| pre.split('.').all(is_valid_pre_id))
+            || !build.is_none_or(|build| build.split('.').all(is_valid_id))
+        {
+            return Err(err());
+        }
+
+        Ok(Self {
+            major,
+            minor,
+            patch,
+            pre: pre.unwrap_or_default().to_string(),
+            build: build.unwrap_or_default().to_string(),
+        })
+    }
+}
+
+/// Parse a numeric version component. Leading zeros are not allowed.
+fn parse_numeric(s: &str) -> Option<u64> {
+    if s.is_empty()
+        || !s.chars().all(|c| c.is_ascii_digit())
+        || (s.len() > 1 && s.starts_with('0'))
+    {
+        return None;
+    }
+    s.parse().ok()
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            write!(f, "-{}", self.pre)?;
+        }
+        if !self.build.is_empty() {
+            write!(f, "+{}", self.build)?;
+        }
+        Ok(())
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| cmp_prerelease(&self.pre, &other.pre))
+            .then_with(|| self.build.cmp(&other.build))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compare prerelease strings by semver precedence.
+fn cmp_prerelease(a: &str, b: &str) -> Ordering {
+    // A release has higher precedence than any prerelease.
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => return Ordering::Equal,
+        (true, false) => return Ordering::Greater,
+        (false, true) => return Ordering::Less,
+        (false, false) => {}
+    }
+
+    let mut a_ids = a.split('.');
+    let mut b_ids = b.split('.');
+    loop {
+        let ord = match (a_ids.next(), b_ids.next()) {
+            (None, None) => return Ordering::Equal,
+            // More identifiers means higher precedence.
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) => {
+                match (a.parse::<u64>().ok(), b.parse::<u64>().ok()) {
+                    (Some(a), Some(b)) => a.cmp(&b),
+                    // Numeric identifiers are lower than alphanumeric.
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => a.cmp(b),
+                }
+            }
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> Version {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(v("1.2.3"), Version::new(1, 2, 3));
+        assert_eq!(
+            v("1.2.3-beta.1+build.5"),
+            Version {
+                major: 1,
+                minor: 2,
+                patch: 3,
+                pre: "beta.1".to_string(),
+                build: "build.5".to_string(),
+            }
+        );
+        assert_eq!(v("1.0.0-x-y").pre, "x-y");
+        assert_eq!(v("1.0.0+a-b").build, "a-b");
+
+        for invalid in [
+            "",
+            "1",
+            "1.2",
+            "1.2.3.4",
+            "01.2.3",
+            "1.2.x",
+            "v1.2.3",
+            "1.2.3-",
+            "1.2.3+",
+            "1.2.3-a..b",
+            "1.2.3-01",
+            "1.2.3-a_b",
+        ] {
+            assert!(invalid.parse::<Version>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        for s in ["1.2.3", "1.2.3-rc.1", "1.2.3+b", "1.2.3-rc.1+b"] {
+            assert_eq!(v(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn test_ord() {
+        // Order from the semver spec.
+        let ordered = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "1.0.1",
+            "1.2.0",
+            "1.10.0",
+            "2.0.0",
+        ];
+        for pair in ordered.windows(2) {
+            assert!(v(pair[0]) < v(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+
+        assert_eq!(v("1.0.0").cmp(&v("1.0.0")), Ordering::Equal);
+        assert_ne!(v("1.0.0+a").cmp(&v("1.0.0+b")), Ordering::Equal);
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Github Actions [workflow commands].
+//!
+//! Workflow commands are special lines written to stdout. They are
+//! used to create annotations that show up in the Actions UI (and on
+//! the relevant lines of files), and to mask secrets in the log.
+//!
+//! [workflow commands]: https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions
+
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+
+/// Severity of an [`Annotation`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum AnnotationLevel {
+    /// Error annotation.
+    Error,
+
+    /// Warning annotation.
+    Warning,
+
+    /// Notice annotation.
+    Notice,
+}
+
+impl AnnotationLevel {
+    fn command(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Notice => "notice",
+        }
+    }
+}
+
+/// A message shown in the Actions UI.
+///
+/// The [`Display`] impl formats the annotation as a workflow command,
+/// with the message and properties escaped as needed.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Annotation {
+    /// Severity of the annotation.
+    pub level: AnnotationLevel,
+
+    /// Message text. This may contain multiple lines.
+    pub message: String,
+
+    /// Custom title for the annotation.
+    pub title: Option<String>,
+
+    /// File the annotation applies to. This should be relative to the
+    /// root of the repository.
+    pub file: Option<PathBuf>,
+
+    /// First line (starting at 1) of `file` the annotation applies to.
+    pub line: Option<usize>,
+
+    /// Last line of `file` the annotation applies to.
+    pub end_line: Option<usize>,
+}
+
+impl Annotation {
+    /// Create an annotation with no properties.
+    pub fn new<S>(level: AnnotationLevel, message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            level,
+            message: message.into(),
+            title: None,
+            file: None,
+            line: None,
+            end_line: None,
+        }
+    }
+
+    /// Create an error annotation.
+    pub fn error<S: Into<String>>(message: S) -> Self {
+        Self::new(AnnotationLevel::Error, message)
+    }
+
+    /// Create a warning annotation.
+    pub fn warning<S: Into<String>>(message: S) -> Self {
+        Self::new(AnnotationLevel::Warning, message)
+    }
+
+    /// Create a notice annotation.
+    pub fn notice<S: Into<String>>(message: S) -> Self {
+        Self::new(AnnotationLevel::Notice, message)
+    }
+
+    /// Set the title.
+    pub fn with_title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Set the file, and optionally the line within that file.
+    pub fn with_file<P>(mut self, file: P, line: Option<usize>) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.file = Some(file.into());
+        self.line = line;
+        self
+    }
+
+    /// Print the annotation to stdout.
+    pub fn emit(&self) {
+        println!("{self}");
+    }
+}
+
+impl Display for Annotation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut props = Vec::new();
+        if let Some(title) = &self.title {
+            props.push(("title", title.clone()));
+        }
+        if let Some(file) = &self.file {
+            props.push(("file", file.display().to_string()));
+        }
+        if let Some(line) = self.line {
+            props.push(("line", line.to_string()));
+        }
+        if let Some(end_line) = self.end_line {
+            props.push(("endLine", end_line.to_string()));
+        }
+
+        write!(f, "::{}", self.level.command())?;
+        for (i, (key, value)) in props.iter().enumerate() {
+            let sep = if i == 0 { ' ' } else { ',' };
+            write!(f, "{sep}{key}={}", escape_property(value))?;
+        }
+        write!(f, "::{}", escape_data(&self.message))
+    }
+}
+
+/// Print an error annotation to stdout.
+pub fn error(message: &str) {
+    Annotation::error(message).emit();
+}
+
+/// Print a warning annotation to stdout.
+pub fn warning(message: &str) {
+    Annotation::warning(message).emit();
+}
+
+/// Print a notice annotation to stdout.
+pub fn notice(message: &str) {
+    Annotation::notice(message).emit();
+}
+
+/// Mask `value` in the rest of the job's log.
+///
+/// Each line of a multi-line value is masked separately.
+pub fn add_mask(value: &str) {
+    for line in value.lines().filter(|line| !line.is_empty()) {
+        println!("::add-mask::{}", escape_data(line));
+    }
+}
+
+/// Escape the message part of a workflow command.
+pub fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a property value of a workflow command.
+pub fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape_data("100%\r\na:b,c"), "100%25%0D%0Aa:b,c");
+        assert_eq!(escape_property("100%\r\na:b,c"), "100%25%0D%0Aa%3Ab%2Cc");
+    }
+
+    #[test]
+    fn test_annotation() {
+        assert_eq!(Annotation::notice("hello").to_string(), "::notice::hello");
+
+        assert_eq!(
+            Annotation::error("line 1\nline 2")
+                .with_title("Release failed")
+                .with_file("foo/Cargo.toml", Some(3))
+                .to_string(),
+            "::error title=Release failed,file=foo/Cargo.toml,line=3::line 1%0Aline 2"
+        );
+
+        let mut annotation =
+            Annotation::warning("w").with_file("a,b:c.toml", None);
+        annotation.end_line = Some(5);
+        assert_eq!(
+            annotation.to_string(),
+            "::warning file=a%2Cb%3Ac.toml,endLine=5::w"
+        );
+    }
+}
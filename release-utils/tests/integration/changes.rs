@@ -0,0 +1,132 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::git;
+use release_utils::changes::*;
+use release_utils::{Package, Repo};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn commit_all(dir: &Path, msg: &str) {
+    git(dir, &["add", "-A"]);
+    git(dir, &["commit", "-m", msg]);
+}
+
+/// Create a workspace with a root package `app`, which depends on
+/// `mid`, which depends on `base`.
+fn create_workspace(dir: &Path) {
+    fs::write(
+        dir.join("Cargo.toml"),
+        r#"
+[workspace]
+members = ["crates/base", "crates/mid"]
+
+[package]
+name = "app"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+mid = { path = "crates/mid" }
+"#,
+    )
+    .unwrap();
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src/lib.rs"), "").unwrap();
+
+    for (name, dep) in [("base", None), ("mid", Some("base"))] {
+        let crate_dir = dir.join("crates").join(name);
+        fs::create_dir_all(crate_dir.join("src")).unwrap();
+        let mut manifest = format!(
+            "[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n"
+        );
+        if let Some(dep) = dep {
+            manifest.push_str(&format!(
+                "\n[dependencies]\n{dep} = {{ path = \"../{dep}\" }}\n"
+            ));
+        }
+        fs::write(crate_dir.join("Cargo.toml"), manifest).unwrap();
+        fs::write(crate_dir.join("src/lib.rs"), "").unwrap();
+    }
+}
+
+#[test]
+fn test_get_package_changes() {
+    let tmp_dir = TempDir::new().unwrap();
+    let dir = tmp_dir.path();
+    git(dir, &["init", "--initial-branch=main"]);
+    git(
+        dir,
+        &["config", "user.email", "release-utils-test@example.com"],
+    );
+    git(dir, &["config", "user.name", "Release Utils Test"]);
+    create_workspace(dir);
+    commit_all(dir, "initial commit");
+    git(dir, &["tag", "base-v0.1.0"]);
+    git(dir, &["tag", "mid-v0.1.0"]);
+    git(dir, &["tag", "app-v0.1.0"]);
+
+    let repo = Repo::open_path(dir).unwrap();
+    let packages: Vec<_> = ["app", "mid", "base"]
+        .iter()
+        .map(|name| Package::with_workspace(*name, dir))
+        .collect();
+
+    // No changes yet.
+    let changes = get_package_changes(&repo, &packages, "HEAD", true).unwrap();
+    assert!(changes.iter().all(|c| !c.has_changes()));
+    assert_eq!(changes[0].last_tag.as_deref(), Some("app-v0.1.0"));
+
+    // Change `base`.
+    fs::write(dir.join("crates/base/src/lib.rs"), "pub fn f() {}").unwrap();
+    commit_all(dir, "change base");
+
+    let changes = get_package_changes(&repo, &packages, "HEAD", false).unwrap();
+    assert!(!changes[0].has_changes());
+    assert!(!changes[1].has_changes());
+    assert_eq!(
+        changes[2],
+        PackageChanges {
+            package: "base".to_string(),
+            last_tag: Some("base-v0.1.0".to_string()),
+            changed_files: vec![PathBuf::from("crates/base/src/lib.rs")],
+            changed_deps: Vec::new(),
+        }
+    );
+
+    // With propagation, packages depending on `base` have changes too.
+    let changes = get_package_changes(&repo, &packages, "HEAD", true).unwrap();
+    assert_eq!(changes[0].changed_deps, ["base"]);
+    assert!(changes[0].changed_files.is_empty());
+    assert_eq!(changes[1].changed_deps, ["base"]);
+    assert!(changes[2].changed_deps.is_empty());
+
+    // Files at the root belong to the root package.
+    fs::write(dir.join("README.md"), "readme").unwrap();
+    commit_all(dir, "add readme");
+    let changes = get_package_changes(&repo, &packages, "HEAD", false).unwrap();
+    assert_eq!(changes[0].changed_files, [PathBuf::from("README.md")]);
+
+    // A package that has never been released.
+    git(dir, &["tag", "--delete", "mid-v0.1.0"]);
+    let changes = get_package_changes(&repo, &packages, "HEAD", false).unwrap();
+    assert_eq!(changes[1].last_tag, None);
+    assert!(changes[1].has_changes());
+
+    // Unknown package.
+    assert!(matches!(
+        get_package_changes(
+            &repo,
+            &[Package::with_workspace("nope", dir)],
+            "HEAD",
+            false
+        ),
+        Err(GetPackageChangesError::PackageNotFound(_))
+    ));
+}
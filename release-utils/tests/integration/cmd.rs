@@ -12,6 +12,7 @@ use std::os::unix::ffi::OsStrExt;
 use std::process::Command;
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_format_cmd() {
     assert_eq!(format_cmd(&Command::new("echo")), "echo");
     assert_eq!(format_cmd(&Command::new("echo").arg("hello")), "echo hello");
@@ -67,6 +68,24 @@ fn test_get_cmd_stdout() {
     }
 }
 
+#[test]
+fn test_get_cmd_output() {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "echo out; echo err >&2; exit 3"]);
+    let output = get_cmd_output(cmd).unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(output.stdout, b"out\n");
+    assert_eq!(output.stderr, b"err\n");
+
+    if let RunCommandError::Launch { cmd, .. } =
+        get_cmd_output(Command::new("does-not-exist")).unwrap_err()
+    {
+        assert_eq!(cmd, "does-not-exist");
+    } else {
+        panic!("incorrect error type");
+    }
+}
+
 #[test]
 fn test_get_cmd_stdout_utf8() {
     let mut cmd = Command::new("echo");
@@ -24,4 +24,52 @@ fn test_get_crate_versions() {
             .unwrap_err(),
         GetCrateVersionsError::NotPublished
     ));
+
+    let mut cargo = CrateRegistry::new();
+    cargo.registry_url = "https://does-not-exist.invalid".to_string();
+    let err = cargo.get_crate_versions("release-utils").unwrap_err();
+    assert!(matches!(err, GetCrateVersionsError::Network { .. }));
+    assert!(err.is_transient());
+}
+
+#[test]
+fn test_get_many_crate_versions() {
+    let cargo = CrateRegistry::new();
+    let results = cargo
+        .get_many_crate_versions(&[
+            "release-utils",
+            "does-not-exist-92452",
+            "auto-release",
+        ])
+        .unwrap();
+    assert_eq!(results.len(), 3);
+
+    let versions = results["release-utils"].as_ref().unwrap();
+    assert!(versions.contains(&"0.4.1".to_string()));
+
+    let versions = results["auto-release"].as_ref().unwrap();
+    assert!(versions.contains(&"0.5.0".to_string()));
+
+    assert!(matches!(
+        results["does-not-exist-92452"],
+        Err(GetCrateVersionsError::NotPublished)
+    ));
+}
+
+#[test]
+fn test_get_crate_checksum() {
+    let cargo = CrateRegistry::new();
+    assert_eq!(
+        cargo
+            .get_crate_checksum("release-utils", "0.4.1")
+            .unwrap()
+            .as_deref(),
+        Some(
+            "02922e087d9f1da9f783ca54f4621f1a156ffc3f8563d66c2d74b5d2d6363ccf"
+        )
+    );
+    assert_eq!(
+        cargo.get_crate_checksum("release-utils", "0.0.0").unwrap(),
+        None
+    );
 }
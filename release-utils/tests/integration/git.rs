@@ -6,8 +6,16 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use release_utils::Repo;
+use crate::{create_repo_with_remote, create_test_repo, git, git_output};
 use release_utils::cmd::*;
+use release_utils::{
+    Commit, History, Identity, InProgressOperation, InvalidTagFormat, Package,
+    PushBranchError, PushBranchOptions, PushTagsError, RejectedRef, ReleaseTag,
+    Repo, RepoOpenError, SigningFormat, TagOptions, TagSigning, Trailer,
+    Version,
+};
+use std::fs;
+use std::path::Path;
 use std::process::Command;
 use tempfile::TempDir;
 
@@ -15,7 +23,14 @@ use tempfile::TempDir;
 fn test_repo_open() {
     let tmp_dir = TempDir::new().unwrap();
 
-    assert!(Repo::open_path(tmp_dir.path()).is_err());
+    assert!(matches!(
+        Repo::open_path(tmp_dir.path()).unwrap_err(),
+        RepoOpenError::NotARepository(_)
+    ));
+    assert!(matches!(
+        Repo::open_path(tmp_dir.path().join("missing")).unwrap_err(),
+        RepoOpenError::NotADirectory(_)
+    ));
 
     // Create a temporary repo.
     let mut cmd = Command::new("git");
@@ -23,7 +38,43 @@ fn test_repo_open() {
     cmd.arg(tmp_dir.path());
     run_cmd(cmd).unwrap();
 
-    assert!(Repo::open_path(tmp_dir.path()).is_ok());
+    let repo = Repo::open_path(tmp_dir.path()).unwrap();
+    let root = tmp_dir.path().canonicalize().unwrap();
+    assert_eq!(repo.path(), root);
+    assert_eq!(repo.git_dir(), root.join(".git"));
+    assert!(repo.has_work_tree());
+}
+
+#[test]
+fn test_repo_open_discovery() {
+    let tmp_dir = TempDir::new().unwrap();
+    let root = tmp_dir.path().canonicalize().unwrap();
+    let main_dir = root.join("main");
+    fs::create_dir(&main_dir).unwrap();
+    create_test_repo(&main_dir);
+
+    // Subdirectory of the work tree.
+    let sub_dir = main_dir.join("a/b");
+    fs::create_dir_all(&sub_dir).unwrap();
+    let repo = Repo::open_path(&sub_dir).unwrap();
+    assert_eq!(repo.path(), main_dir);
+
+    // Worktree, where `.git` is a file.
+    git(&main_dir, &["worktree", "add", "../wt"]);
+    let repo = Repo::open_path(root.join("wt")).unwrap();
+    assert_eq!(repo.path(), root.join("wt"));
+    assert!(repo.git_dir().starts_with(main_dir.join(".git/worktrees")));
+    assert_eq!(
+        repo.get_uncommitted_changes().unwrap(),
+        Vec::<String>::new()
+    );
+
+    // Bare repo.
+    git(&root, &["init", "--bare", "bare.git"]);
+    let repo = Repo::open_path(root.join("bare.git")).unwrap();
+    assert_eq!(repo.path(), root.join("bare.git"));
+    assert_eq!(repo.git_dir(), root.join("bare.git"));
+    assert!(!repo.has_work_tree());
 }
 
 #[test]
@@ -71,3 +122,709 @@ fn test_get_commit_message() {
         "Here's the body.\n"
     );
 }
+
+#[test]
+fn test_head_sha() {
+    let tmp_dir = TempDir::new().unwrap();
+    let repo = create_test_repo(tmp_dir.path());
+
+    let sha = repo.head_sha().unwrap();
+    assert_eq!(sha.len(), 40);
+    assert!(sha.chars().all(|c| c.is_ascii_hexdigit()));
+}
+
+#[test]
+fn test_get_uncommitted_changes() {
+    let tmp_dir = TempDir::new().unwrap();
+    let repo = create_test_repo(tmp_dir.path());
+    assert!(repo.get_uncommitted_changes().unwrap().is_empty());
+
+    fs::write(tmp_dir.path().join("new.txt"), "hello").unwrap();
+    assert_eq!(repo.get_uncommitted_changes().unwrap(), ["new.txt"]);
+
+    fs::write(tmp_dir.path().join(".gitignore"), "*.txt").unwrap();
+    assert_eq!(repo.get_uncommitted_changes().unwrap(), [".gitignore"]);
+
+    // Renamed files and paths that git would otherwise quote.
+    fs::write(tmp_dir.path().join("old.rs"), "fn main() {}").unwrap();
+    git(tmp_dir.path(), &["add", "old.rs", ".gitignore"]);
+    git(tmp_dir.path(), &["commit", "-m", "add old.rs"]);
+    git(tmp_dir.path(), &["mv", "old.rs", "new name.rs"]);
+    fs::write(tmp_dir.path().join("quo\"te\u{e9}.rs"), "").unwrap();
+    let mut changes = repo.get_uncommitted_changes().unwrap();
+    changes.sort();
+    assert_eq!(changes, ["new name.rs", "quo\"te\u{e9}.rs"]);
+}
+
+#[test]
+fn test_is_commit_pushed() {
+    let tmp_dir = TempDir::new().unwrap();
+    let remote_dir = tmp_dir.path().join("remote");
+    let local_dir = tmp_dir.path().join("local");
+    fs::create_dir(&local_dir).unwrap();
+    git(tmp_dir.path(), &["init", "--bare", "remote"]);
+    let repo = create_test_repo(&local_dir);
+    git(
+        &local_dir,
+        &["remote", "add", "origin", remote_dir.to_str().unwrap()],
+    );
+
+    let sha = repo.head_sha().unwrap();
+    assert!(!repo.is_commit_pushed(&sha).unwrap());
+
+    git(&local_dir, &["push", "origin", "main"]);
+    assert!(repo.is_commit_pushed(&sha).unwrap());
+}
+
+#[test]
+fn test_identity_parse() {
+    assert_eq!(
+        Identity::parse("Jane Doe <jane@example.com>"),
+        Some(Identity::new("Jane Doe", "jane@example.com"))
+    );
+    assert_eq!(Identity::parse("Jane Doe"), None);
+    assert_eq!(Identity::parse("<jane@example.com>"), None);
+    assert_eq!(
+        Identity::new("Jane Doe", "jane@example.com").to_string(),
+        "Jane Doe <jane@example.com>"
+    );
+}
+
+#[test]
+fn test_make_git_tag() {
+    let tmp_dir = TempDir::new().unwrap();
+    let dir = tmp_dir.path();
+    let repo = create_test_repo(dir);
+    let sha = repo.head_sha().unwrap();
+
+    // Lightweight tag.
+    repo.make_git_tag("light", &sha, &TagOptions::default())
+        .unwrap();
+    assert_eq!(git_output(dir, &["cat-file", "-t", "light"]), "commit");
+
+    // Annotated tag with a custom tagger.
+    repo.make_git_tag(
+        "annotated",
+        &sha,
+        &TagOptions {
+            message: Some("foo 1.0.0\n\nChanges.".to_string()),
+            tagger: Some(Identity::new("Tagger", "tagger@example.com")),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(git_output(dir, &["cat-file", "-t", "annotated"]), "tag");
+    assert_eq!(
+        git_output(
+            dir,
+            &[
+                "tag",
+                "--list",
+                "--format=%(taggername) %(taggeremail)%0a%(contents)",
+                "annotated"
+            ]
+        ),
+        "Tagger <tagger@example.com>\nfoo 1.0.0\n\nChanges."
+    );
+    assert_eq!(git_output(dir, &["rev-parse", "annotated^{}"]), sha);
+}
+
+#[test]
+fn test_get_tag_commit() {
+    let tmp_dir = TempDir::new().unwrap();
+    let repo = create_test_repo(tmp_dir.path());
+    let sha = repo.head_sha().unwrap();
+    git(tmp_dir.path(), &["commit", "--allow-empty", "-m", "second"]);
+
+    git(tmp_dir.path(), &["tag", "light", &sha]);
+    git(
+        tmp_dir.path(),
+        &["tag", "-a", "-m", "msg", "annotated", &sha],
+    );
+    assert_eq!(repo.get_tag_commit("light").unwrap(), Some(sha.clone()));
+    assert_eq!(repo.get_tag_commit("annotated").unwrap(), Some(sha));
+    assert_eq!(repo.get_tag_commit("missing").unwrap(), None);
+}
+
+#[test]
+fn test_make_git_tag_ssh_signed() {
+    let tmp_dir = TempDir::new().unwrap();
+    let dir = tmp_dir.path().join("repo");
+    fs::create_dir(&dir).unwrap();
+    let repo = create_test_repo(&dir);
+    let sha = repo.head_sha().unwrap();
+
+    let key = tmp_dir.path().join("key");
+    let mut cmd = Command::new("ssh-keygen");
+    cmd.args(["-q", "-t", "ed25519", "-N", "", "-f"]);
+    cmd.arg(&key);
+    run_cmd(cmd).unwrap();
+
+    repo.make_git_tag(
+        "signed",
+        &sha,
+        &TagOptions {
+            signing: Some(TagSigning {
+                format: SigningFormat::Ssh,
+                key: Some(key.to_str().unwrap().to_string()),
+            }),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let contents = git_output(&dir, &["cat-file", "tag", "signed"]);
+    assert!(contents.contains("-----BEGIN SSH SIGNATURE-----"));
+}
+
+#[test]
+fn test_push_git_tags() {
+    let tmp_dir = TempDir::new().unwrap();
+    let (remote_dir, _local_dir, repo) =
+        create_repo_with_remote(tmp_dir.path());
+    let sha = repo.head_sha().unwrap();
+    for tag in ["a", "b", "stray"] {
+        repo.make_git_tag(tag, &sha, &TagOptions::default())
+            .unwrap();
+    }
+
+    // Only the requested tags are pushed.
+    repo.push_git_tags("upstream", &["a", "b"]).unwrap();
+    assert_eq!(git_output(&remote_dir, &["tag", "--list"]), "a\nb");
+}
+
+#[test]
+fn test_remote_tags() {
+    let tmp_dir = TempDir::new().unwrap();
+    let (_remote_dir, local_dir, repo) =
+        create_repo_with_remote(tmp_dir.path());
+    let sha = repo.head_sha().unwrap();
+    git(&local_dir, &["tag", "foo-v1.0.0"]);
+    git(&local_dir, &["tag", "-a", "-m", "msg", "foo-v1.1.0"]);
+    git(&local_dir, &["tag", "bar-v1.0.0"]);
+    repo.push_git_tags("upstream", &["foo-v1.0.0", "foo-v1.1.0", "bar-v1.0.0"])
+        .unwrap();
+    git(&local_dir, &["tag", "foo-v2.0.0"]);
+
+    // Only tags on the remote are returned, and annotated tags are
+    // peeled.
+    let tags = repo.remote_tags("upstream", &["foo-v*"]).unwrap();
+    assert_eq!(
+        tags.into_iter().collect::<Vec<_>>(),
+        [
+            ("foo-v1.0.0".to_string(), sha.clone()),
+            ("foo-v1.1.0".to_string(), sha.clone()),
+        ]
+    );
+    assert_eq!(repo.remote_tags("upstream", &[]).unwrap().len(), 3);
+    assert!(
+        repo.remote_tags("upstream", &["baz-v*"])
+            .unwrap()
+            .is_empty()
+    );
+
+    // Fetch a single tag into a fresh clone.
+    let clone_dir = tmp_dir.path().join("clone");
+    fs::create_dir(&clone_dir).unwrap();
+    let clone = create_test_repo(&clone_dir);
+    git(
+        &clone_dir,
+        &[
+            "remote",
+            "add",
+            "upstream",
+            tmp_dir.path().join("remote").to_str().unwrap(),
+        ],
+    );
+    clone.fetch_tags("upstream", &["foo-v1.1.0"]).unwrap();
+    assert_eq!(git_output(&clone_dir, &["tag", "--list"]), "foo-v1.1.0");
+    assert_eq!(clone.get_tag_commit("foo-v1.1.0").unwrap(), Some(sha));
+
+    // A local tag pointing elsewhere is not overwritten.
+    git(&clone_dir, &["commit", "--allow-empty", "-m", "other"]);
+    git(&clone_dir, &["tag", "foo-v1.0.0"]);
+    let clone_sha = clone.head_sha().unwrap();
+    assert!(clone.fetch_tags("upstream", &["foo-v1.0.0"]).is_err());
+    assert_eq!(clone.get_tag_commit("foo-v1.0.0").unwrap(), Some(clone_sha));
+}
+
+#[test]
+fn test_default_branch() {
+    let tmp_dir = TempDir::new().unwrap();
+    let (remote_dir, local_dir, repo) = create_repo_with_remote(tmp_dir.path());
+    git(&remote_dir, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+    assert_eq!(
+        repo.get_remote_url("upstream").unwrap(),
+        remote_dir.to_str().unwrap()
+    );
+
+    // The remote's HEAD doesn't point at anything yet.
+    assert_eq!(repo.get_default_branch("upstream").unwrap(), None);
+
+    git(&local_dir, &["push", "upstream", "main"]);
+    assert_eq!(
+        repo.get_default_branch("upstream").unwrap().as_deref(),
+        Some("main")
+    );
+
+    let pushed = repo.head_sha().unwrap();
+    git(&local_dir, &["commit", "--allow-empty", "-m", "unpushed"]);
+    let unpushed = repo.head_sha().unwrap();
+    assert!(
+        repo.is_commit_in_remote_branch(&pushed, "upstream", "main")
+            .unwrap()
+    );
+    assert!(
+        !repo
+            .is_commit_in_remote_branch(&unpushed, "upstream", "main")
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_in_progress_operation() {
+    let tmp_dir = TempDir::new().unwrap();
+    let dir = tmp_dir.path();
+    let repo = create_test_repo(dir);
+    assert_eq!(repo.get_in_progress_operation(), None);
+
+    // Create a merge conflict.
+    fs::write(dir.join("file"), "a").unwrap();
+    git(dir, &["add", "file"]);
+    git(dir, &["commit", "-m", "a"]);
+    git(dir, &["checkout", "-b", "other", "HEAD^"]);
+    fs::write(dir.join("file"), "b").unwrap();
+    git(dir, &["add", "file"]);
+    git(dir, &["commit", "-m", "b"]);
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(dir).args(["merge", "main"]);
+    assert!(!cmd.output().unwrap().status.success());
+
+    assert_eq!(
+        repo.get_in_progress_operation(),
+        Some(InProgressOperation::Merge)
+    );
+    git(dir, &["merge", "--abort"]);
+    assert_eq!(repo.get_in_progress_operation(), None);
+}
+
+#[test]
+fn test_commit_and_push_branch() {
+    let tmp_dir = TempDir::new().unwrap();
+    let (remote_dir, local_dir, repo) = create_repo_with_remote(tmp_dir.path());
+    let base = repo.head_sha().unwrap();
+    git(&local_dir, &["checkout", "--detach"]);
+
+    fs::write(local_dir.join("a"), "a").unwrap();
+    fs::write(local_dir.join("b"), "b").unwrap();
+    repo.stage_files(&[Path::new("a")]).unwrap();
+    let bot = Identity::new("Release Bot", "bot@example.com");
+    let sha = repo
+        .commit("chore: release foo 1.2.0\n", Some(&bot))
+        .unwrap();
+    assert_eq!(
+        git_output(&local_dir, &["log", "-1", "--format=%an <%ae>|%cn|%B"]),
+        "Release Bot <bot@example.com>|Release Bot|chore: release foo 1.2.0"
+    );
+    // Only the staged file was committed.
+    assert_eq!(
+        git_output(&local_dir, &["show", "--name-only", "--format=", &sha]),
+        "a"
+    );
+    assert_eq!(repo.get_uncommitted_changes().unwrap(), ["b"]);
+
+    repo.create_branch("release/foo", &sha).unwrap();
+    repo.push_branch("upstream", "release/foo", &PushBranchOptions::default())
+        .unwrap();
+    assert_eq!(
+        git_output(&remote_dir, &["rev-parse", "refs/heads/release/foo"]),
+        sha
+    );
+
+    // Rewrite the branch so that pushing isn't a fast-forward.
+    git(&local_dir, &["branch", "--force", "release/foo", &base]);
+    let err = repo
+        .push_branch("upstream", "release/foo", &PushBranchOptions::default())
+        .unwrap_err();
+    assert!(matches!(err, PushBranchError::Rejected(_)));
+
+    // The lease fails if the remote isn't at the expected commit.
+    let err = repo
+        .push_branch(
+            "upstream",
+            "release/foo",
+            &PushBranchOptions {
+                force_with_lease: true,
+                expected_sha: Some(base.clone()),
+            },
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("stale info"), "{err}");
+
+    repo.push_branch(
+        "upstream",
+        "release/foo",
+        &PushBranchOptions {
+            force_with_lease: true,
+            expected_sha: Some(sha),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        git_output(&remote_dir, &["rev-parse", "refs/heads/release/foo"]),
+        base
+    );
+}
+
+#[test]
+fn test_delete_tag() {
+    let tmp_dir = TempDir::new().unwrap();
+    let (remote_dir, local_dir, repo) = create_repo_with_remote(tmp_dir.path());
+    git(&local_dir, &["tag", "a"]);
+    git(&local_dir, &["tag", "b"]);
+    repo.push_git_tags("upstream", &["a", "b"]).unwrap();
+
+    repo.delete_tag("a", None).unwrap();
+    assert_eq!(git_output(&local_dir, &["tag", "--list"]), "b");
+    assert_eq!(git_output(&remote_dir, &["tag", "--list"]), "a\nb");
+
+    repo.delete_tag("a", Some("upstream")).unwrap();
+    assert_eq!(git_output(&remote_dir, &["tag", "--list"]), "b");
+
+    // Deleting a missing tag is not an error.
+    repo.delete_tag("a", Some("upstream")).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_push_git_tags_rejected() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp_dir = TempDir::new().unwrap();
+    let (remote_dir, _local_dir, repo) =
+        create_repo_with_remote(tmp_dir.path());
+    let sha = repo.head_sha().unwrap();
+    for tag in ["a", "protected"] {
+        repo.make_git_tag(tag, &sha, &TagOptions::default())
+            .unwrap();
+    }
+
+    // Simulate a tag protection rule.
+    let hook = remote_dir.join("hooks/pre-receive");
+    fs::write(
+        &hook,
+        "#!/bin/sh\n\
+         while read old new name; do\n\
+           case $name in refs/tags/protected) exit 1;; esac\n\
+         done\n",
+    )
+    .unwrap();
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let PushTagsError::Rejected(rejected) = repo
+        .push_git_tags("upstream", &["a", "protected"])
+        .unwrap_err()
+    else {
+        panic!("unexpected error");
+    };
+    let reason = "[remote rejected] (pre-receive hook declined)";
+    assert_eq!(
+        rejected,
+        [
+            RejectedRef {
+                git_ref: "refs/tags/a".to_string(),
+                reason: reason.to_string(),
+            },
+            RejectedRef {
+                git_ref: "refs/tags/protected".to_string(),
+                reason: reason.to_string(),
+            },
+        ]
+    );
+
+    // The push is atomic.
+    assert_eq!(git_output(&remote_dir, &["tag", "--list"]), "");
+}
+
+#[test]
+fn test_shallow_history() {
+    let tmp_dir = TempDir::new().unwrap();
+    let remote_dir = tmp_dir.path().join("remote");
+    fs::create_dir(&remote_dir).unwrap();
+    let remote = create_test_repo(&remote_dir);
+    let first = remote.head_sha().unwrap();
+    for msg in ["second", "third", "fourth"] {
+        git(&remote_dir, &["commit", "--allow-empty", "-m", msg]);
+    }
+
+    // Shallow clones require a file URL rather than a plain path.
+    let url = format!("file://{}", remote_dir.display());
+    git(tmp_dir.path(), &["clone", "--depth=1", &url, "local"]);
+    let repo = Repo::open_path(tmp_dir.path().join("local")).unwrap();
+    assert!(repo.is_shallow().unwrap());
+    assert!(!remote.is_shallow().unwrap());
+
+    let count = || git_output(repo.path(), &["rev-list", "--count", "HEAD"]);
+    assert_eq!(count(), "1");
+    repo.ensure_history("origin", &History::Deepen(1)).unwrap();
+    assert_eq!(count(), "2");
+
+    // Missing commits are fetched as needed.
+    assert_eq!(
+        repo.get_commit_message_subject(&first).unwrap(),
+        "initial commit"
+    );
+
+    repo.ensure_history("origin", &History::Full).unwrap();
+    assert!(!repo.is_shallow().unwrap());
+    assert_eq!(count(), "4");
+}
+
+#[test]
+fn test_shallow_commits() {
+    let tmp_dir = TempDir::new().unwrap();
+    let remote_dir = tmp_dir.path().join("remote");
+    fs::create_dir(&remote_dir).unwrap();
+    create_test_repo(&remote_dir);
+    git(&remote_dir, &["tag", "v1"]);
+    for msg in ["second", "third", "fourth"] {
+        git(&remote_dir, &["commit", "--allow-empty", "-m", msg]);
+    }
+
+    let url = format!("file://{}", remote_dir.display());
+    git(
+        tmp_dir.path(),
+        &["clone", "--depth=2", "--origin=upstream", &url, "local"],
+    );
+    let mut repo = Repo::open_path(tmp_dir.path().join("local")).unwrap();
+    assert_eq!(repo.history_remote(), "origin");
+    repo.set_history_remote("upstream");
+
+    // The range is within the shallow history, so nothing is fetched.
+    let commits = repo.commits("HEAD~1..HEAD", &[]).unwrap();
+    assert_eq!(commits.len(), 1);
+    assert!(repo.is_shallow().unwrap());
+
+    // The range reaches the shallow boundary.
+    let commits = repo.commits("HEAD", &[]).unwrap();
+    assert_eq!(commits.len(), 4);
+    assert!(!repo.is_shallow().unwrap());
+
+    // Errors are not mistaken for a missing history.
+    assert!(repo.commits("missing..HEAD", &[]).is_err());
+
+    // The start of the range is outside the shallow history.
+    git(
+        tmp_dir.path(),
+        &["clone", "--depth=2", "--origin=upstream", &url, "local2"],
+    );
+    let mut repo = Repo::open_path(tmp_dir.path().join("local2")).unwrap();
+    repo.set_history_remote("upstream");
+    let commits = repo.commits("v1..HEAD", &[]).unwrap();
+    assert_eq!(commits.len(), 3);
+    assert!(!repo.is_shallow().unwrap());
+}
+
+#[test]
+fn test_shallow_remote_branch() {
+    let tmp_dir = TempDir::new().unwrap();
+    let remote_dir = tmp_dir.path().join("remote");
+    fs::create_dir(&remote_dir).unwrap();
+    let remote = create_test_repo(&remote_dir);
+    let first = remote.head_sha().unwrap();
+    // More commits than the first fetch gets.
+    for i in 0..60 {
+        git(
+            &remote_dir,
+            &["commit", "--allow-empty", "-m", &i.to_string()],
+        );
+    }
+
+    let url = format!("file://{}", remote_dir.display());
+    git(tmp_dir.path(), &["clone", "--depth=1", &url, "local"]);
+    let local_dir = tmp_dir.path().join("local");
+    let repo = Repo::open_path(&local_dir).unwrap();
+    assert!(
+        repo.is_commit_in_remote_branch(&first, "origin", "main")
+            .unwrap()
+    );
+
+    git(&local_dir, &["config", "user.email", "test@example.com"]);
+    git(&local_dir, &["config", "user.name", "Test"]);
+    git(&local_dir, &["commit", "--allow-empty", "-m", "unpushed"]);
+    let unpushed = repo.head_sha().unwrap();
+    assert!(
+        !repo
+            .is_commit_in_remote_branch(&unpushed, "origin", "main")
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_commits() {
+    let tmp_dir = TempDir::new().unwrap();
+    let dir = tmp_dir.path();
+    let repo = create_test_repo(dir);
+    let first = repo.head_sha().unwrap();
+    git(dir, &["tag", "v1"]);
+
+    fs::create_dir(dir.join("a")).unwrap();
+    fs::write(dir.join("a/file"), "a").unwrap();
+    git(dir, &["add", "a"]);
+    git(
+        dir,
+        &[
+            "commit",
+            "-m",
+            "add a\n\nSome details.\n\nRelease: foo, bar\nSigned-off-by: Someone <someone@example.com>",
+        ],
+    );
+    let second = repo.head_sha().unwrap();
+
+    fs::write(dir.join("b"), "b").unwrap();
+    git(dir, &["add", "b"]);
+    git(dir, &["commit", "-m", "add b"]);
+    let third = repo.head_sha().unwrap();
+
+    let commits = repo.commits("v1..HEAD", &[]).unwrap();
+    let shas: Vec<_> = commits.iter().map(|c| c.sha.as_str()).collect();
+    assert_eq!(shas, [third.as_str(), second.as_str()]);
+
+    let identity =
+        Identity::new("Release Utils Test", "release-utils-test@example.com");
+    let commit = &commits[1];
+    assert_eq!(
+        *commit,
+        Commit {
+            sha: second.clone(),
+            parents: vec![first.clone()],
+            author: identity.clone(),
+            committer: identity,
+            date: commit.date.clone(),
+            subject: "add a".to_string(),
+            body: "Some details.\n\nRelease: foo, bar\nSigned-off-by: Someone <someone@example.com>".to_string(),
+            trailers: vec![
+                Trailer {
+                    key: "Release".to_string(),
+                    value: "foo, bar".to_string(),
+                },
+                Trailer {
+                    key: "Signed-off-by".to_string(),
+                    value: "Someone <someone@example.com>".to_string(),
+                },
+            ],
+        }
+    );
+    assert!(commit.date.starts_with("20"));
+    assert!(commits[0].trailers.is_empty());
+    assert_eq!(commits[0].body, "");
+
+    // Filter by path.
+    let commits = repo.commits("HEAD", &[Path::new("a")]).unwrap();
+    assert_eq!(commits.len(), 1);
+    assert_eq!(commits[0].sha, second);
+
+    // All history.
+    assert_eq!(repo.commits("HEAD", &[]).unwrap().len(), 3);
+}
+
+#[test]
+fn test_trailers() {
+    let tmp_dir = TempDir::new().unwrap();
+    let repo = create_test_repo(tmp_dir.path());
+    let message =
+        "Subject\n\nBody.\n\nRelease: foo,\n  bar\nRelease-Type: minor\n";
+    git(tmp_dir.path(), &["commit", "--allow-empty", "-m", message]);
+    let sha = repo.head_sha().unwrap();
+
+    let expected = [
+        Trailer {
+            key: "Release".to_string(),
+            value: "foo, bar".to_string(),
+        },
+        Trailer {
+            key: "Release-Type".to_string(),
+            value: "minor".to_string(),
+        },
+    ];
+    assert_eq!(repo.get_commit_trailers(&sha).unwrap(), expected);
+    assert_eq!(repo.parse_trailers(message).unwrap(), expected);
+    assert!(expected[0].has_key("release"));
+
+    assert!(repo.parse_trailers("Subject\n\nBody.").unwrap().is_empty());
+}
+
+#[test]
+fn test_release_tags() {
+    let tmp_dir = TempDir::new().unwrap();
+    let repo = create_test_repo(tmp_dir.path());
+    for tag in [
+        "foo-v1.0.0",
+        "foo-v1.10.0",
+        "foo-v1.2.0",
+        "foo-v2.0.0-rc.1",
+        "foo-vbad",
+        "foo-bar-v9.0.0",
+        "v3.0.0",
+    ] {
+        git(tmp_dir.path(), &["tag", tag]);
+    }
+
+    let tag = |s: &str| ReleaseTag {
+        version: s.split_once('v').unwrap().1.parse().unwrap(),
+        tag: s.to_string(),
+    };
+
+    let pkg = Package::new("foo");
+    assert_eq!(
+        pkg.get_release_tags(&repo).unwrap(),
+        [
+            tag("foo-v1.0.0"),
+            tag("foo-v1.2.0"),
+            tag("foo-v1.10.0"),
+            tag("foo-v2.0.0-rc.1"),
+        ]
+    );
+    assert_eq!(
+        pkg.get_latest_release(&repo, false).unwrap(),
+        Some(tag("foo-v1.10.0"))
+    );
+    assert_eq!(
+        pkg.get_latest_release(&repo, true).unwrap(),
+        Some(tag("foo-v2.0.0-rc.1"))
+    );
+    assert_eq!(
+        pkg.get_latest_release_before(&repo, &Version::new(1, 10, 0), false)
+            .unwrap(),
+        Some(tag("foo-v1.2.0"))
+    );
+    assert_eq!(
+        pkg.get_latest_release_before(&repo, &Version::new(2, 0, 0), true)
+            .unwrap(),
+        Some(tag("foo-v2.0.0-rc.1"))
+    );
+    assert_eq!(
+        pkg.get_latest_release_before(&repo, &Version::new(1, 0, 0), true)
+            .unwrap(),
+        None
+    );
+
+    let mut pkg = Package::new("foo");
+    pkg.set_tag_format("v{version}").unwrap();
+    assert_eq!(pkg.get_git_tag_name("1.2.3"), "v1.2.3");
+    assert_eq!(pkg.parse_git_tag("v1.2.3"), Some(Version::new(1, 2, 3)));
+    assert_eq!(pkg.parse_git_tag("foo-v1.2.3"), None);
+    assert_eq!(
+        pkg.set_tag_format("release"),
+        Err(InvalidTagFormat("release".to_string()))
+    );
+    assert_eq!(pkg.tag_format(), "v{version}");
+    assert_eq!(
+        pkg.get_latest_release(&repo, false).unwrap(),
+        Some(tag("v3.0.0"))
+    );
+
+    assert_eq!(
+        Package::new("baz").get_latest_release(&repo, true).unwrap(),
+        None
+    );
+}
@@ -6,7 +6,9 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use release_utils::github::{CreateRelease, Gh};
+use release_utils::Identity;
+use release_utils::github::{CreateRelease, CreateTag, Gh};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
 use tempfile::TempDir;
@@ -16,6 +18,22 @@ echo "${@}" > OUT_PATH
 exit EXIT_VAL
 "#;
 
+/// Fake `gh api` that records every call and returns canned responses
+/// for the tag endpoints.
+const API_TEMPLATE: &str = r#"#!/bin/sh
+echo "${@}" >> OUT_PATH
+case "$2" in
+  --paginate)
+    echo "refs/tags/foo-v1.0.0 commit c1"
+    echo "refs/tags/foo-v1.1.0 tag t2"
+    echo "refs/tags/foo-v1.10.0 commit c3"
+    ;;
+  */git/tags/t2) echo c2 ;;
+  */git/tags) echo t4 ;;
+esac
+exit EXIT_VAL
+"#;
+
 struct Script {
     exe_path: PathBuf,
     output_path: PathBuf,
@@ -23,10 +41,14 @@ struct Script {
 
 impl Script {
     fn create(tmp_dir: &TempDir, exit_val: u32) -> Self {
+        Self::from_template(tmp_dir, WRITE_ARGS_TEMPLATE, exit_val)
+    }
+
+    fn from_template(tmp_dir: &TempDir, template: &str, exit_val: u32) -> Self {
         let exe_path = tmp_dir.path().join("write_args.sh");
         let output_path = tmp_dir.path().join("args");
 
-        let content = WRITE_ARGS_TEMPLATE
+        let content = template
             .replace("OUT_PATH", output_path.to_str().unwrap())
             .replace("EXIT_VAL", &exit_val.to_string());
 
@@ -66,6 +88,7 @@ fn test_gh_new_default() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_gh_does_release_exist() {
     let tmp_dir = TempDir::new().unwrap();
 
@@ -104,3 +127,98 @@ fn test_gh_create_release() {
         "release create --verify-tag --title title --notes l1\nl2 some-tag f1 f2\n"
     );
 }
+
+#[test]
+fn test_gh_delete_release() {
+    let tmp_dir = TempDir::new().unwrap();
+
+    let script = Script::create(&tmp_dir, 0);
+    let gh = Gh::with_exe(script.exe_path);
+    gh.delete_release("some-tag").unwrap();
+    assert_eq!(
+        fs::read_to_string(script.output_path).unwrap(),
+        "release delete some-tag --yes\n"
+    );
+}
+
+#[test]
+fn test_gh_create_tag() {
+    let tmp_dir = TempDir::new().unwrap();
+
+    // Lightweight tag: only the ref is created.
+    let script = Script::from_template(&tmp_dir, API_TEMPLATE, 0);
+    let gh = Gh::with_exe(script.exe_path.clone());
+    gh.create_tag(
+        "o/r",
+        CreateTag {
+            tag: "foo-v1.0.0".to_string(),
+            sha: "c1".to_string(),
+            message: None,
+            tagger: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(&script.output_path).unwrap(),
+        "api repos/o/r/git/refs -f ref=refs/tags/foo-v1.0.0 -f sha=c1\n"
+    );
+
+    // Annotated tag: the ref points at the new tag object.
+    fs::remove_file(&script.output_path).unwrap();
+    gh.create_tag(
+        "o/r",
+        CreateTag {
+            tag: "foo-v1.0.0".to_string(),
+            sha: "c1".to_string(),
+            message: Some("foo 1.0.0".to_string()),
+            tagger: Some(Identity::new("A", "a@example.com")),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(&script.output_path).unwrap(),
+        "api repos/o/r/git/tags -f tag=foo-v1.0.0 -f message=foo 1.0.0 \
+         -f object=c1 -f type=commit -f tagger[name]=A \
+         -f tagger[email]=a@example.com --jq .sha\n\
+         api repos/o/r/git/refs -f ref=refs/tags/foo-v1.0.0 -f sha=t4\n"
+    );
+
+    let script = Script::create(&tmp_dir, 1);
+    let gh = Gh::with_exe(script.exe_path);
+    assert!(
+        gh.create_tag(
+            "o/r",
+            CreateTag {
+                tag: "foo-v1.0.0".to_string(),
+                sha: "c1".to_string(),
+                message: None,
+                tagger: None,
+            },
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn test_gh_get_tag_commits() {
+    let tmp_dir = TempDir::new().unwrap();
+
+    let script = Script::from_template(&tmp_dir, API_TEMPLATE, 0);
+    let gh = Gh::with_exe(script.exe_path);
+    // The annotated tag is resolved to its commit, and refs that match
+    // the prefix but not the pattern are skipped.
+    assert_eq!(
+        gh.get_tag_commits("o/r", &["foo-v1.?.0"]).unwrap(),
+        BTreeMap::from([
+            ("foo-v1.0.0".to_string(), "c1".to_string()),
+            ("foo-v1.1.0".to_string(), "c2".to_string()),
+        ])
+    );
+    let calls = fs::read_to_string(&script.output_path).unwrap();
+    let calls: Vec<_> = calls.lines().collect();
+    assert_eq!(calls.len(), 2);
+    assert!(calls[0].starts_with(
+        "api --paginate repos/o/r/git/matching-refs/tags/foo-v1. --jq "
+    ));
+    assert_eq!(calls[1], "api repos/o/r/git/tags/t2 --jq .object.sha");
+}
@@ -6,8 +6,95 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+mod changes;
 mod cmd;
 mod crate_registry;
 mod git;
 mod github;
 mod package;
+mod provenance;
+mod release;
+
+use release_utils::cmd::{get_cmd_stdout_utf8, run_cmd};
+use release_utils::{CrateRegistry, HttpConfig, Repo};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+
+/// Run a git command in `dir`.
+fn git(dir: &Path, args: &[&str]) {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C");
+    cmd.arg(dir);
+    cmd.args(args);
+    run_cmd(cmd).unwrap();
+}
+
+/// Run a git command in `dir` and get its trimmed stdout.
+fn git_output(dir: &Path, args: &[&str]) -> String {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C");
+    cmd.arg(dir);
+    cmd.args(args);
+    get_cmd_stdout_utf8(cmd).unwrap().trim().to_string()
+}
+
+/// Create a repo in `dir` with an identity configured and one empty
+/// commit.
+fn create_test_repo(dir: &Path) -> Repo {
+    git(dir, &["init", "--initial-branch=main"]);
+    git(
+        dir,
+        &["config", "user.email", "release-utils-test@example.com"],
+    );
+    git(dir, &["config", "user.name", "Release Utils Test"]);
+    git(dir, &["commit", "--allow-empty", "-m", "initial commit"]);
+    Repo::open_path(dir).unwrap()
+}
+
+/// Create a bare remote named "upstream" and a local repo using it.
+/// Returns the remote dir, the local dir, and the local repo.
+fn create_repo_with_remote(dir: &Path) -> (PathBuf, PathBuf, Repo) {
+    let remote_dir = dir.join("remote");
+    let local_dir = dir.join("local");
+    fs::create_dir(&local_dir).unwrap();
+    git(dir, &["init", "--bare", "remote"]);
+    let repo = create_test_repo(&local_dir);
+    git(
+        &local_dir,
+        &["remote", "add", "upstream", remote_dir.to_str().unwrap()],
+    );
+    (remote_dir, local_dir, repo)
+}
+
+/// Serve `index_file` (or 404 if `None`) for every request, standing in
+/// for a sparse registry. Returns a registry using the server.
+fn serve_registry(index_file: Option<String>) -> CrateRegistry {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut registry = CrateRegistry::with_http_config(HttpConfig::default());
+    registry.registry_url =
+        format!("http://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            // Skip the request headers.
+            let mut reader = BufReader::new(&stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let response = match &index_file {
+                Some(body) => format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                ),
+                None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+            };
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    registry
+}
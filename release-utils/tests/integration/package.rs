@@ -30,6 +30,10 @@ fn test_package_local_version() {
 
     let pkg = Package::with_workspace("foo", tmp_dir.path());
     assert_eq!(pkg.get_local_version().unwrap(), "0.1.0");
+    assert_eq!(
+        pkg.get_manifest_path().unwrap(),
+        tmp_dir.path().canonicalize().unwrap().join("Cargo.toml")
+    );
 
     let pkg = Package::with_workspace("invalid", tmp_dir.path());
     if let GetLocalVersionError::PackageNotFound(name) =
@@ -0,0 +1,62 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::{create_repo_with_remote, git, git_output};
+use release_utils::provenance::{
+    ReleaseRecord, get_release_records, write_release_record,
+};
+use tempfile::TempDir;
+
+#[test]
+fn test_release_notes() {
+    let tmp_dir = TempDir::new().unwrap();
+    let (remote_dir, local_dir, repo) = create_repo_with_remote(tmp_dir.path());
+    let sha = repo.head_sha().unwrap();
+    let notes_ref = "refs/notes/releases";
+
+    assert_eq!(repo.get_note(notes_ref, &sha).unwrap(), None);
+    assert!(
+        get_release_records(&repo, notes_ref, &sha)
+            .unwrap()
+            .is_empty()
+    );
+    // The remote doesn't have the notes ref yet.
+    repo.fetch_notes("upstream", notes_ref).unwrap();
+
+    let foo = ReleaseRecord {
+        package: "foo".to_string(),
+        version: "1.2.0".to_string(),
+        registry: "crates.io".to_string(),
+        crate_sha256: Some("abc123".to_string()),
+        timestamp: "2024-01-02T03:04:05Z".to_string(),
+        run_url: None,
+    };
+    let bar = ReleaseRecord {
+        package: "bar".to_string(),
+        ..foo.clone()
+    };
+    write_release_record(&repo, notes_ref, &sha, &foo).unwrap();
+    write_release_record(&repo, notes_ref, &sha, &bar).unwrap();
+    assert_eq!(
+        get_release_records(&repo, notes_ref, &sha).unwrap(),
+        [foo.clone(), bar.clone()]
+    );
+
+    repo.push_notes("upstream", notes_ref).unwrap();
+    let remote_note =
+        git_output(&remote_dir, &["notes", "--ref=releases", "show", &sha]);
+    assert_eq!(remote_note, format!("{foo}\n{bar}").trim());
+
+    // Local notes are replaced by the remote's.
+    git(&local_dir, &["notes", "--ref=releases", "remove", &sha]);
+    repo.fetch_notes("upstream", notes_ref).unwrap();
+    assert_eq!(
+        get_release_records(&repo, notes_ref, &sha).unwrap(),
+        [foo, bar]
+    );
+}
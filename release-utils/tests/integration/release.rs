@@ -0,0 +1,150 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::{create_repo_with_remote, git, git_output, serve_registry};
+use release_utils::Package;
+use release_utils::release::{
+    PackageRelease, ReleasePackageError, RollbackError, RollbackOptions,
+    release_package, rollback_release,
+};
+use tempfile::TempDir;
+
+#[test]
+fn test_release_package_tag_mismatch() {
+    let tmp_dir = TempDir::new().unwrap();
+    let (_remote_dir, local_dir, repo) =
+        create_repo_with_remote(tmp_dir.path());
+    let old_sha = repo.head_sha().unwrap();
+    git(&local_dir, &["commit", "--allow-empty", "-m", "new"]);
+    let new_sha = repo.head_sha().unwrap();
+
+    let package = Package::new("release-utils");
+    let version = package.get_local_version().unwrap();
+    let tag = package.get_git_tag_name(&version);
+    git(&local_dir, &["tag", &tag, &old_sha]);
+    repo.push_git_tags("upstream", &[&tag]).unwrap();
+
+    // Unpublished version: fail rather than publishing.
+    let unpublished = serve_registry(None);
+    let err =
+        release_package(&repo, &package, &new_sha, &unpublished, "upstream")
+            .unwrap_err();
+    let ReleasePackageError::TagMismatch {
+        tag: err_tag,
+        tag_commit,
+        commit,
+    } = err
+    else {
+        panic!("unexpected error: {err}");
+    };
+    assert_eq!(err_tag, tag);
+    assert_eq!(tag_commit, old_sha);
+    assert_eq!(commit, new_sha);
+
+    // Already published from the tag: nothing to do.
+    let published =
+        serve_registry(Some(format!("{{\"vers\":\"{version}\"}}\n")));
+    let release =
+        release_package(&repo, &package, &new_sha, &published, "upstream")
+            .unwrap();
+    assert!(!release.is_new());
+
+    // A stale local tag that isn't on the remote is an error even if
+    // the version was published.
+    git(tmp_dir.path(), &["init", "--bare", "empty"]);
+    git(
+        &local_dir,
+        &[
+            "remote",
+            "add",
+            "empty",
+            tmp_dir.path().join("empty").to_str().unwrap(),
+        ],
+    );
+    let err = release_package(&repo, &package, &new_sha, &published, "empty")
+        .unwrap_err();
+    assert!(matches!(err, ReleasePackageError::TagMismatch { .. }));
+}
+
+#[test]
+fn test_rollback_release() {
+    let tmp_dir = TempDir::new().unwrap();
+    let (remote_dir, local_dir, repo) = create_repo_with_remote(tmp_dir.path());
+    let sha = repo.head_sha().unwrap();
+    git(&local_dir, &["tag", "foo-v1.0.0"]);
+    git(&local_dir, &["tag", "bar-v1.0.0"]);
+    git(&local_dir, &["commit", "--allow-empty", "-m", "other"]);
+    git(&local_dir, &["tag", "baz-v1.0.0"]);
+    repo.push_git_tags("upstream", &["foo-v1.0.0", "bar-v1.0.0", "baz-v1.0.0"])
+        .unwrap();
+
+    let release = |package: &str| PackageRelease {
+        package: package.to_string(),
+        version: "1.0.0".to_string(),
+        tag: format!("{package}-v1.0.0"),
+        published: false,
+        tagged: true,
+    };
+    let unpublished = serve_registry(None);
+    let published = serve_registry(Some("{\"vers\":\"1.0.0\"}\n".to_string()));
+    let mut opt = RollbackOptions {
+        remote: Some("upstream".to_string()),
+        ..Default::default()
+    };
+
+    // Nothing happens without confirmation.
+    assert!(matches!(
+        rollback_release(&repo, &unpublished, &sha, &[release("foo")], &opt)
+            .unwrap_err(),
+        RollbackError::NotConfirmed
+    ));
+    assert_eq!(
+        git_output(&remote_dir, &["tag", "--list"]),
+        "bar-v1.0.0\nbaz-v1.0.0\nfoo-v1.0.0"
+    );
+
+    opt.confirm = true;
+
+    // Tags pointing at another commit are not deleted.
+    let err = rollback_release(
+        &repo,
+        &unpublished,
+        &sha,
+        &[release("foo"), release("baz")],
+        &opt,
+    )
+    .unwrap_err();
+    assert!(
+        matches!(err, RollbackError::TagMismatch { tag, .. } if tag == "baz-v1.0.0")
+    );
+    assert_eq!(
+        git_output(&remote_dir, &["tag", "--list"]),
+        "bar-v1.0.0\nbaz-v1.0.0\nfoo-v1.0.0"
+    );
+
+    rollback_release(&repo, &unpublished, &sha, &[release("foo")], &opt)
+        .unwrap();
+    assert_eq!(
+        git_output(&remote_dir, &["tag", "--list"]),
+        "bar-v1.0.0\nbaz-v1.0.0"
+    );
+    assert_eq!(
+        git_output(&local_dir, &["tag", "--list"]),
+        "bar-v1.0.0\nbaz-v1.0.0"
+    );
+
+    // Tags of published versions are skipped unless allowed.
+    rollback_release(&repo, &published, &sha, &[release("bar")], &opt).unwrap();
+    assert_eq!(
+        git_output(&remote_dir, &["tag", "--list"]),
+        "bar-v1.0.0\nbaz-v1.0.0"
+    );
+    opt.allow_published = true;
+    rollback_release(&repo, &published, &sha, &[release("bar")], &opt).unwrap();
+    assert_eq!(git_output(&remote_dir, &["tag", "--list"]), "baz-v1.0.0");
+}